@@ -1,6 +1,9 @@
 use clap::{Parser};
-use pulcinella::server::{bind_socket, run_controlplane, run_mock, Mode, SequentialState};
-use std::net::SocketAddr;
+use pulcinella::server::{
+    bind_socket, run_controlplane, run_mockplane, CertificateAuthority, Interception, Mode,
+    SequentialState, TlsIdentity,
+};
+use std::{fs, net::SocketAddr, path::Path, time::Duration};
 use tokio::join;
 use tracing::{info, level_filters::LevelFilter, Level};
 use tracing_subscriber::{EnvFilter, FmtSubscriber};
@@ -10,10 +13,52 @@ use tracing_subscriber::{EnvFilter, FmtSubscriber};
 struct Opts {
     #[clap(short, long, default_value = "false", env = "PROXY")]
     proxy_mode: bool,
+    /// Proxy unmatched requests and record each answer as a mock
+    #[clap(long, default_value = "false", env = "RECORD", conflicts_with_all = ["proxy_mode", "playback"])]
+    record: bool,
+    /// Serve only installed mocks, failing unmatched requests with a 500
+    #[clap(
+        long,
+        default_value = "false",
+        env = "PLAYBACK",
+        conflicts_with_all = ["proxy_mode", "upstream"]
+    )]
+    playback: bool,
+    /// Reverse proxy unmatched requests to this origin, e.g. http://localhost:8080
+    #[clap(long, env = "UPSTREAM")]
+    upstream: Option<String>,
     #[clap(short, long, default_value = "0", env = "CONTROL_PORT")]
     control_port: u16,
     #[clap(short, long, default_value = "0", env = "MOCK_PORT")]
     mock_port: u16,
+    #[clap(short, long, default_value = "false", env = "ISOLATED")]
+    isolated: bool,
+    #[clap(long, default_value = "false", env = "INSTANCE_LISTENERS")]
+    instance_listeners: bool,
+    /// Decrypt HTTPS sent through the proxy using a local certificate authority
+    #[clap(long, default_value = "false", env = "INTERCEPT")]
+    intercept: bool,
+    /// Certificate of the interception authority, generated if missing
+    #[clap(long, default_value = "pulcinella-ca.pem", env = "CA_CERT")]
+    ca_cert: String,
+    /// Private key of the interception authority, generated if missing
+    #[clap(long, default_value = "pulcinella-ca-key.pem", env = "CA_KEY")]
+    ca_key: String,
+    /// Extra CA certificate to trust for intercepted upstreams, e.g. a self-signed server
+    #[clap(long, env = "UPSTREAM_CA")]
+    upstream_ca: Option<String>,
+    /// Serve the mock and control ports over HTTPS
+    #[clap(long, default_value = "false", env = "TLS")]
+    tls: bool,
+    /// Certificate chain for HTTPS, self-signed and saved here if missing
+    #[clap(long, default_value = "pulcinella-tls.pem", env = "TLS_CERT")]
+    tls_cert: String,
+    /// Private key for HTTPS, generated and saved here if missing
+    #[clap(long, default_value = "pulcinella-tls-key.pem", env = "TLS_KEY")]
+    tls_key: String,
+    /// Seconds an instance may sit idle before it is evicted
+    #[clap(long, env = "INSTANCE_TTL")]
+    instance_ttl: Option<u64>,
 }
 
 #[tokio::main]
@@ -38,14 +83,99 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
 
     let mock = bind_socket(mock_addr).await?;
     let control = bind_socket(control_addr).await?;
-    let state = SequentialState::new(mock.port);
+    let proxy_mode = opts.proxy_mode || opts.upstream.is_some();
+    let mode = match (proxy_mode, opts.record, opts.playback) {
+        (_, true, _) => Mode::Record,
+        (true, _, _) => Mode::Proxy,
+        (_, _, true) => Mode::Playback,
+        _ => Mode::Mock,
+    };
+    let state = SequentialState::new(mock.port).with_mode(mode);
+    let state = if opts.isolated { state.isolated() } else { state };
+    let state = if opts.instance_listeners { state.with_instance_listeners() } else { state };
+    let state = match &opts.upstream {
+        Some(upstream) => state.with_upstream(upstream)?,
+        None => state,
+    };
+    let state = if opts.intercept {
+        let authority = load_authority(&opts.ca_cert, &opts.ca_key)?;
+        let interception = Interception::new(authority);
+        let interception = match &opts.upstream_ca {
+            Some(path) => interception.trust_upstream(&fs::read_to_string(path)?)?,
+            None => interception,
+        };
+        state.with_interception(interception)
+    } else {
+        state
+    };
+    let state = if opts.tls {
+        state.with_tls(load_identity(&opts.tls_cert, &opts.tls_key)?)
+    } else {
+        state
+    };
+    let state = match opts.instance_ttl {
+        Some(secs) => state.with_idle_ttl(Duration::from_secs(secs)),
+        None => state,
+    };
 
-    info!("Control Port on http://127.0.0.1:{}/", control.port);
-    info!("{} on http://127.0.0.1:{}/", if opts.proxy_mode { "Proxy" } else { "Mock" }, mock.port);
+    let scheme = if opts.tls { "https" } else { "http" };
+    info!("Control Port on {}://127.0.0.1:{}/", scheme, control.port);
+    info!("{:?} on {}://127.0.0.1:{}/", mode, scheme, mock.port);
+    if let Some(upstream) = &opts.upstream {
+        info!("Forwarding unmatched requests to {}", upstream);
+    }
 
     let control = run_controlplane(control.listener, state.clone());
-    let mock = run_mock(mock.listener, state, if opts.proxy_mode { Mode::Proxy } else { Mode::Mock });
+    let mock = run_mockplane(mock.listener, state);
     let (cp_result, mock_result) = join!(control, mock);
 
     cp_result.and(mock_result)
 }
+
+/// Loads the interception authority, generating and saving one the first time
+/// so clients only need to trust it once.
+fn load_authority(
+    cert_path: &str,
+    key_path: &str,
+) -> Result<CertificateAuthority, Box<dyn std::error::Error + Send + Sync>> {
+    if Path::new(cert_path).exists() && Path::new(key_path).exists() {
+        let authority = CertificateAuthority::from_pem(
+            &fs::read_to_string(cert_path)?,
+            &fs::read_to_string(key_path)?,
+        )?;
+        info!("Intercepting HTTPS with the authority in {}", cert_path);
+        return Ok(authority);
+    }
+    let authority = CertificateAuthority::generate()?;
+    fs::write(cert_path, authority.cert_pem())?;
+    fs::write(key_path, authority.key_pem())?;
+    info!(
+        "Intercepting HTTPS with a new authority; trust {} in your clients",
+        cert_path
+    );
+    Ok(authority)
+}
+
+/// Loads the HTTPS certificate, generating and saving a self-signed one the
+/// first time so clients can be pointed at it.
+fn load_identity(
+    cert_path: &str,
+    key_path: &str,
+) -> Result<TlsIdentity, Box<dyn std::error::Error + Send + Sync>> {
+    if Path::new(cert_path).exists() && Path::new(key_path).exists() {
+        let identity = TlsIdentity::from_pem(
+            &fs::read_to_string(cert_path)?,
+            &fs::read_to_string(key_path)?,
+        )?;
+        info!("Serving HTTPS with the certificate in {}", cert_path);
+        return Ok(identity);
+    }
+    let identity = TlsIdentity::self_signed()?;
+    fs::write(cert_path, identity.cert_pem())?;
+    fs::write(key_path, identity.key_pem())?;
+    info!(
+        "Serving HTTPS with a new self-signed certificate; trust {} in your clients",
+        cert_path
+    );
+    Ok(identity)
+}
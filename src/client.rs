@@ -1,26 +1,49 @@
+use std::time::Duration;
+
 use thiserror::Error;
 
 use crate::{
     interchange::{
-        Command, InstallError, InstallResponse, InstanceId, InstanceResponse, WhenRules, MockRule, ThenState,
+        Command, EmptyResponse, InstallError, InstallResponse, InstanceId, InstanceResponse,
+        JsonBody, JsonPredicate, JsonSelector, PathMatcher, QueryValue,
     },
     network_client::{ClientNetworkError, NetworkClient},
 };
-pub use crate::interchange::Method;
+pub use crate::interchange::{
+    CannedResponse, CorsPolicy, Delay, Dribble, Fault, JsonCondition, Method, Mismatch, MockId,
+    MockRule, NearMiss, Passthrough, RecordedRequest, Scenario, SequencePolicy, ThenState,
+    UnmatchedReport, WhenRules, SCENARIO_STARTED,
+};
 
+#[derive(Debug)]
 pub struct Client {
     control_plane_url: String,
     instance: InstanceId,
     mock_url: String,
+    network: NetworkClient,
 }
 
 impl Client {
     pub async fn new(control_plane_url: &str) -> Result<Self, ClientError> {
-        let body = NetworkClient::send::<Command, InstanceResponse, InstanceResponse>(
-            control_plane_url,
-            &Command::CreateInstance,
-        )
-        .await;
+        Self::connect(control_plane_url, NetworkClient::default()).await
+    }
+
+    /// Like [`Client::new`], but also trusts `ca_pem` when the control plane
+    /// serves HTTPS, such as the server's self-signed certificate.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn with_ca(control_plane_url: &str, ca_pem: &str) -> Result<Self, ClientError> {
+        let network =
+            NetworkClient::trusting(ca_pem).map_err(|_| ClientError::InvalidCertificate)?;
+        Self::connect(control_plane_url, network).await
+    }
+
+    async fn connect(control_plane_url: &str, network: NetworkClient) -> Result<Self, ClientError> {
+        let body = network
+            .send::<Command, InstanceResponse, InstanceResponse>(
+                control_plane_url,
+                &Command::CreateInstance,
+            )
+            .await;
         let response = body.map_err(|err| match err {
             ClientNetworkError::FailedToConnectToMockServer => {
                 ClientError::FailedToConnectToMockServer
@@ -32,10 +55,11 @@ impl Client {
             control_plane_url: String::from(control_plane_url),
             instance: response.instance,
             mock_url: response.url,
+            network,
         })
     }
 
-    pub fn when<F>(&self, when: F) -> MockBuilder<WhenRules>
+    pub fn when<F>(&self, when: F) -> MockBuilder<'_, WhenRules>
     where
         F: FnOnce(WhenBuilder) -> WhenBuilder,
     {
@@ -46,19 +70,160 @@ impl Client {
         self.mock_url.clone()
     }
 
-    async fn send_command(&self, command: Command) -> Result<(), ClientError> {
-        NetworkClient::send::<Command, InstallResponse, InstallError>(
-            &self.control_plane_url,
-            &command,
-        )
+    /// Every request the mock port received for this instance, matched or
+    /// not, oldest first.
+    pub async fn received_requests(&self) -> Result<Vec<RecordedRequest>, ClientError> {
+        self.send_command(Command::ReceivedRequests {
+            instance: self.instance.clone(),
+        })
+        .await
+    }
+
+    /// The requests no mock matched, each with the installed mocks ranked by
+    /// how closely they came to matching it.
+    pub async fn unmatched_requests(&self) -> Result<Vec<RecordedRequest>, ClientError> {
+        let requests = self.received_requests().await?;
+        Ok(requests
+            .into_iter()
+            .filter(|request| request.matched.is_none())
+            .collect())
+    }
+
+    /// Sets how the mock port answers cross-origin requests for this instance.
+    pub async fn configure_cors(&self, cors: CorsPolicy) -> Result<(), ClientError> {
+        self.send_command::<EmptyResponse>(Command::ConfigureCors {
+            instance: self.instance.clone(),
+            cors,
+        })
         .await
         .map(|_| ())
-        .map_err(|e| match e {
-            ClientNetworkError::Response(InstallError::InstanceNotFound) => {
-                ClientError::InstanceNoLongerValid
-            }
-            _ => ClientError::FailedToConnectToMockServer,
+    }
+
+    /// The current state of every scenario this instance knows about.
+    pub async fn scenarios(&self) -> Result<Vec<Scenario>, ClientError> {
+        self.send_command(Command::ScenarioStates {
+            instance: self.instance.clone(),
+        })
+        .await
+    }
+
+    /// Moves every scenario back to [`SCENARIO_STARTED`].
+    pub async fn reset_scenarios(&self) -> Result<(), ClientError> {
+        self.send_command::<EmptyResponse>(Command::ResetScenarios {
+            instance: self.instance.clone(),
+        })
+        .await
+        .map(|_| ())
+    }
+
+    /// The mock rules recorded from proxied traffic so far, which can be
+    /// saved and later installed with [`Client::install`] for playback.
+    pub async fn recorded_mocks(&self) -> Result<Vec<MockRule>, ClientError> {
+        self.send_command(Command::RecordedMocks {
+            instance: self.instance.clone(),
         })
+        .await
+    }
+
+    /// Installs a ready-made rule, such as one exported by
+    /// [`Client::recorded_mocks`].
+    pub async fn install(&self, rule: MockRule) -> Result<Mock<'_>, ClientError> {
+        let command = Command::InstallMock {
+            mock: Box::new(rule.clone()),
+            instance: self.instance.clone(),
+        };
+        let response = self.send_command::<InstallResponse>(command).await?;
+        Ok(Mock {
+            id: response.id,
+            rule,
+            client: self,
+        })
+    }
+
+    /// Removes every mock installed on this instance.
+    pub async fn reset(&self) -> Result<(), ClientError> {
+        self.send_command::<EmptyResponse>(Command::ResetMocks {
+            instance: self.instance.clone(),
+        })
+        .await
+        .map(|_| ())
+    }
+
+    /// Deletes this instance from the server, stopping its listener if it has
+    /// one.
+    pub async fn delete(self) -> Result<(), ClientError> {
+        self.send_command::<EmptyResponse>(Command::DeleteInstance {
+            instance: self.instance.clone(),
+        })
+        .await
+        .map(|_| ())
+    }
+
+    /// Deletes the instance from the server when the returned guard is
+    /// dropped. Deletion happens in the background; use [`Client::delete`]
+    /// to wait for it.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn guard(self) -> InstanceGuard {
+        InstanceGuard(self)
+    }
+
+    async fn send_command<U>(&self, command: Command) -> Result<U, ClientError>
+    where
+        U: serde::de::DeserializeOwned,
+    {
+        self.network
+            .send::<Command, U, InstallError>(&self.control_plane_url, &command)
+            .await
+            .map_err(|e| match e {
+                ClientNetworkError::Response(InstallError::InstanceNotFound) => {
+                    ClientError::InstanceNoLongerValid
+                }
+                ClientNetworkError::Response(InstallError::InvalidMockRule(_)) => {
+                    ClientError::FailedToInstallMockRule
+                }
+                ClientNetworkError::Response(InstallError::MockNotFound) => {
+                    ClientError::MockNotFound
+                }
+                _ => ClientError::FailedToConnectToMockServer,
+            })
+    }
+}
+
+/// A [`Client`] whose instance is deleted from the server on drop.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug)]
+pub struct InstanceGuard(Client);
+
+#[cfg(not(target_arch = "wasm32"))]
+impl std::ops::Deref for InstanceGuard {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        &self.0
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Drop for InstanceGuard {
+    fn drop(&mut self) {
+        let control_plane_url = self.0.control_plane_url.clone();
+        let network = self.0.network.clone();
+        let command = Command::DeleteInstance {
+            instance: self.0.instance.clone(),
+        };
+        // Drop can't await, and blocking here could stall a server running
+        // on the caller's runtime, so delete from a thread of its own.
+        std::thread::spawn(move || {
+            let Ok(runtime) = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+            else {
+                return;
+            };
+            let _ = runtime.block_on(
+                network.send::<Command, EmptyResponse, InstallError>(&control_plane_url, &command),
+            );
+        });
     }
 }
 
@@ -89,29 +254,113 @@ impl<'a> MockBuilder<'a, WhenRules> {
 }
 
 impl<'a> MockBuilder<'a, WhenThenState> {
-    // TODO should this return an ID to be used to delete the mock?
-    pub async fn send(self) -> Result<(), ClientError> {
-        let mock = Command::InstallMock {
-            mock: MockRule {
-                when: self.state.when_rules,
-                then: self.state.then_state,
-            },
-            instance: self.client.instance.clone(),
+    /// Only answer `times` requests, after which other mocks get a chance to
+    /// match.
+    pub fn times(mut self, times: u32) -> Self {
+        self.state.times = Some(times);
+        self
+    }
+
+    pub async fn send(self) -> Result<Mock<'a>, ClientError> {
+        let rule = MockRule {
+            when: self.state.when_rules,
+            then: self.state.then_state,
+            times: self.state.times,
         };
-        self.client.send_command(mock).await
+        self.client.install(rule).await
+    }
+}
+
+/// A mock installed on the server, which can be removed again without
+/// resetting the rest of the instance.
+#[derive(Debug)]
+pub struct Mock<'a> {
+    id: MockId,
+    rule: MockRule,
+    client: &'a Client,
+}
+
+impl<'a> Mock<'a> {
+    pub fn id(&self) -> &MockId {
+        &self.id
+    }
+
+    pub fn rule(&self) -> &MockRule {
+        &self.rule
+    }
+
+    /// The requests this mock has answered, oldest first.
+    pub async fn hits(&self) -> Result<Vec<RecordedRequest>, ClientError> {
+        self.client
+            .send_command(Command::MockHits {
+                instance: self.client.instance.clone(),
+                mock: self.id.clone(),
+            })
+            .await
+    }
+
+    /// Panics unless this mock has answered exactly `times` requests.
+    pub async fn assert_called_times(&self, times: usize) {
+        let hits = self
+            .hits()
+            .await
+            .unwrap_or_else(|err| panic!("Failed to fetch hits for mock {}: {err}", self.id));
+        assert_eq!(
+            times,
+            hits.len(),
+            "Expected mock {} to be called {} times but it was called {} times",
+            self.id,
+            times,
+            hits.len()
+        );
+    }
+
+    pub async fn delete(self) -> Result<(), ClientError> {
+        self.client
+            .send_command::<EmptyResponse>(Command::DeleteMock {
+                instance: self.client.instance.clone(),
+                mock: self.id,
+            })
+            .await
+            .map(|_| ())
     }
 }
 
 #[derive(Default)]
 pub struct WhenBuilder {
     method: Option<Method>,
-    match_path: String,
+    match_path: Option<PathMatcher>,
     form_data: Vec<(String, String)>,
+    headers: Vec<(String, String)>,
+    query: Vec<(String, QueryValue)>,
+    exact_query: bool,
+    json_body: Option<JsonBody>,
+    json_predicates: Vec<JsonPredicate>,
+    scenario: Option<Scenario>,
 }
 
 impl WhenBuilder {
     pub fn path(mut self, path: &str) -> Self {
-        self.match_path = String::from(path);
+        self.match_path = Some(PathMatcher::Exact(String::from(path)));
+        self
+    }
+
+    /// Match any path the regular expression matches in full.
+    pub fn path_regex(mut self, pattern: &str) -> Self {
+        self.match_path = Some(PathMatcher::Regex(String::from(pattern)));
+        self
+    }
+
+    /// Match paths by glob, e.g. `/users/*/orders` or `/files/**`.
+    pub fn path_glob(mut self, glob: &str) -> Self {
+        self.match_path = Some(PathMatcher::Glob(String::from(glob)));
+        self
+    }
+
+    /// Match paths by template, e.g. `/users/{id}/orders/{orderId}`. The
+    /// captured segments are available on the recorded request.
+    pub fn path_template(mut self, template: &str) -> Self {
+        self.match_path = Some(PathMatcher::Template(String::from(template)));
         self
     }
 
@@ -126,11 +375,101 @@ impl WhenBuilder {
         self
     }
 
+    /// Only match requests carrying this header value. Header names are
+    /// compared case-insensitively, values exactly.
+    pub fn header(mut self, name: impl AsRef<str>, value: impl AsRef<str>) -> Self {
+        self.headers
+            .push((name.as_ref().to_string(), value.as_ref().to_string()));
+        self
+    }
+
+    /// Only match requests whose query string has this parameter set to `value`.
+    pub fn query(mut self, name: impl AsRef<str>, value: impl AsRef<str>) -> Self {
+        self.query.push((
+            name.as_ref().to_string(),
+            QueryValue::Exact(value.as_ref().to_string()),
+        ));
+        self
+    }
+
+    /// Only match requests whose query string has this parameter as a bare
+    /// flag with no value, such as `?debug`.
+    pub fn query_present(mut self, name: impl AsRef<str>) -> Self {
+        self.query
+            .push((name.as_ref().to_string(), QueryValue::Present));
+        self
+    }
+
+    /// Only match requests whose query string has this parameter, whatever
+    /// its value.
+    pub fn query_any(mut self, name: impl AsRef<str>) -> Self {
+        self.query
+            .push((name.as_ref().to_string(), QueryValue::Any));
+        self
+    }
+
+    /// Only match requests whose query string has no parameters besides the
+    /// ones given with [`WhenBuilder::query`] and its siblings.
+    pub fn exact_query(mut self) -> Self {
+        self.exact_query = true;
+        self
+    }
+
+    /// Only match requests whose JSON body equals `body`, ignoring key order
+    /// and whitespace.
+    pub fn json_body(mut self, body: serde_json::Value) -> Self {
+        self.json_body = Some(JsonBody::Exact(body));
+        self
+    }
+
+    /// Only match requests whose JSON body contains `body`; extra keys and
+    /// array elements in the request are ignored.
+    pub fn json_body_partial(mut self, body: serde_json::Value) -> Self {
+        self.json_body = Some(JsonBody::Partial(body));
+        self
+    }
+
+    /// Only match requests whose JSON body satisfies `condition` at the
+    /// JSONPath `path`, e.g. `$.order.items[0].sku`.
+    pub fn json_path(mut self, path: impl AsRef<str>, condition: JsonCondition) -> Self {
+        self.json_predicates.push(JsonPredicate {
+            selector: JsonSelector::Path(path.as_ref().to_string()),
+            condition,
+        });
+        self
+    }
+
+    /// Only match requests whose JSON body satisfies `condition` at the
+    /// JSON Pointer `pointer`, e.g. `/order/items/0/sku`.
+    pub fn json_pointer(mut self, pointer: impl AsRef<str>, condition: JsonCondition) -> Self {
+        self.json_predicates.push(JsonPredicate {
+            selector: JsonSelector::Pointer(pointer.as_ref().to_string()),
+            condition,
+        });
+        self
+    }
+
+    /// Only match while the scenario `name` is in `state`. Scenarios start in
+    /// [`SCENARIO_STARTED`].
+    pub fn in_scenario(mut self, name: &str, state: &str) -> Self {
+        self.scenario = Some(Scenario {
+            name: String::from(name),
+            state: String::from(state),
+        });
+        self
+    }
+
     pub(crate) fn build(self) -> WhenRules {
         WhenRules {
-            match_path: self.match_path,
+            match_path: self.match_path.unwrap_or(PathMatcher::Exact(String::new())),
             form_data: self.form_data,
             method: self.method,
+            headers: self.headers,
+            query: self.query,
+            exact_query: self.exact_query,
+            json_body: self.json_body,
+            json_predicates: self.json_predicates,
+            scenario: self.scenario,
         }
     }
 }
@@ -139,6 +478,14 @@ pub struct ThenBuilder {
     status: u16,
     headers: Vec<(String, String)>,
     body: Vec<u8>,
+    delay: Option<Delay>,
+    dribble: Option<Dribble>,
+    fault: Option<Fault>,
+    transition_to: Option<String>,
+    responses: Vec<CannedResponse>,
+    sequence: SequencePolicy,
+    templated: bool,
+    passthrough: Option<Passthrough>,
 }
 
 impl ThenBuilder {
@@ -147,6 +494,14 @@ impl ThenBuilder {
             status: 0,
             headers: vec![],
             body: vec![],
+            delay: None,
+            dribble: None,
+            fault: None,
+            transition_to: None,
+            responses: vec![],
+            sequence: SequencePolicy::default(),
+            templated: false,
+            passthrough: None,
         }
     }
 
@@ -165,15 +520,123 @@ impl ThenBuilder {
         self
     }
 
+    /// Waits before answering, either a fixed `Duration` or a random
+    /// [`Delay`].
+    pub fn delay(mut self, delay: impl Into<Delay>) -> Self {
+        self.delay = Some(delay.into());
+        self
+    }
+
+    /// Sends the body `chunk_size` bytes at a time, one chunk every
+    /// `interval`, to simulate a slow network.
+    pub fn dribble(mut self, chunk_size: usize, interval: Duration) -> Self {
+        self.dribble = Some(Dribble {
+            chunk_size,
+            interval,
+        });
+        self
+    }
+
+    /// Breaks the connection instead of answering; the status, headers and
+    /// body are only used by [`Fault::HeadersThenStall`].
+    pub fn fault(mut self, fault: Fault) -> Self {
+        self.fault = Some(fault);
+        self
+    }
+
+    /// Moves the scenario named in [`WhenBuilder::in_scenario`] to `state`
+    /// once this mock has answered.
+    pub fn transition_to(mut self, state: &str) -> Self {
+        self.transition_to = Some(String::from(state));
+        self
+    }
+
+    /// Answers the next request with `response` once the responses before it
+    /// have been used.
+    pub fn followed_by<F>(mut self, response: F) -> Self
+    where
+        F: FnOnce(ResponseBuilder) -> ResponseBuilder,
+    {
+        self.responses
+            .push(response(ResponseBuilder::default()).build());
+        self
+    }
+
+    /// Starts over from the first response once every response has been
+    /// used, rather than repeating the last one.
+    pub fn cycle(mut self) -> Self {
+        self.sequence = SequencePolicy::Cycle;
+        self
+    }
+
+    /// Fills `{{...}}` placeholders in the body and header values from the
+    /// request: `{{path.id}}`, `{{query.q}}`, `{{header.x-name}}`,
+    /// `{{form.field}}`, `{{json.user.id}}`, `{{uuid}}`, `{{now}}`,
+    /// `{{timestamp}}` and `{{counter}}`.
+    pub fn templated(mut self) -> Self {
+        self.templated = true;
+        self
+    }
+
+    /// Forwards matching requests upstream and answers with the upstream's
+    /// response, even when the server isn't in proxy mode.
+    pub fn passthrough(mut self, passthrough: Passthrough) -> Self {
+        self.passthrough = Some(passthrough);
+        self
+    }
+
     fn build(self, when_rules: WhenRules) -> WhenThenState {
         let then_state = ThenState {
             status: self.status,
             headers: self.headers,
             body: self.body,
+            delay: self.delay,
+            dribble: self.dribble,
+            fault: self.fault,
+            transition_to: self.transition_to,
+            responses: self.responses,
+            sequence: self.sequence,
+            templated: self.templated,
+            passthrough: self.passthrough,
         };
         WhenThenState {
             when_rules,
             then_state,
+            times: None,
+        }
+    }
+}
+
+/// A further response in a sequence; delays, faults and the other settings
+/// apply to the mock as a whole, so only the status, headers and body vary.
+#[derive(Default)]
+pub struct ResponseBuilder {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl ResponseBuilder {
+    pub fn status(mut self, status: u16) -> Self {
+        self.status = status;
+        self
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((String::from(name), String::from(value)));
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    fn build(self) -> CannedResponse {
+        CannedResponse {
+            status: self.status,
+            headers: self.headers,
+            body: self.body,
         }
     }
 }
@@ -181,6 +644,7 @@ impl ThenBuilder {
 pub struct WhenThenState {
     when_rules: WhenRules,
     then_state: ThenState,
+    times: Option<u32>,
 }
 
 #[derive(Error, Debug, PartialEq)]
@@ -189,8 +653,12 @@ pub enum ClientError {
     FailedToConnectToMockServer,
     #[error("Failed to create test instance")]
     FailedToCreateTestInstance,
-    #[error("Mock instance has been replaced with a new instance")]
+    #[error("Mock instance has been replaced, deleted or has expired")]
     InstanceNoLongerValid,
     #[error("Failed to install mock rule into server")]
     FailedToInstallMockRule,
+    #[error("Mock rule is not installed on the server")]
+    MockNotFound,
+    #[error("Invalid CA certificate")]
+    InvalidCertificate,
 }
@@ -1,4 +1,4 @@
-use std::error::Error;
+use std::{error::Error, sync::Arc};
 
 use async_trait::async_trait;
 use http_body_util::{BodyExt, Full};
@@ -7,9 +7,15 @@ use hyper::{
     Request, Response,
 };
 use hyper_util::rt::TokioIo;
+use rustls::{
+    crypto::{ring, CryptoProvider},
+    pki_types::ServerName,
+    ClientConfig, RootCertStore,
+};
 use serde::de::DeserializeOwned;
 use thiserror::Error;
 use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
 pub struct HyperHelpers;
 
 impl HyperHelpers {
@@ -25,7 +31,48 @@ impl HyperHelpers {
         let stream = TcpStream::connect(address)
             .await
             .map_err(|_| RequestError::CannotConnect)?;
-        let io = TokioIo::new(stream);
+        Self::send_over(TokioIo::new(stream), request).await
+    }
+
+    /// Like [`HyperHelpers::send`], but over TLS verified by `config`.
+    pub async fn send_tls<B>(
+        config: &Arc<ClientConfig>,
+        address: &str,
+        request: Request<B>,
+    ) -> Result<Response<Incoming>, RequestError>
+    where
+        B: Body + 'static + Send,
+        B::Data: Send,
+        B::Error: Into<Box<dyn Error + Send + Sync>>,
+    {
+        let host = address
+            .rsplit_once(':')
+            .map_or(address, |(host, _)| host)
+            .trim_start_matches('[')
+            .trim_end_matches(']');
+        let name =
+            ServerName::try_from(host.to_string()).map_err(|_| RequestError::CannotConnect)?;
+        let stream = TcpStream::connect(address)
+            .await
+            .map_err(|_| RequestError::CannotConnect)?;
+        let stream = TlsConnector::from(config.clone())
+            .connect(name, stream)
+            .await
+            .map_err(|_| RequestError::TlsHandshake)?;
+        Self::send_over(TokioIo::new(stream), request).await
+    }
+
+    /// Sends the request over an already established connection.
+    pub async fn send_over<B, I>(
+        io: I,
+        request: Request<B>,
+    ) -> Result<Response<Incoming>, RequestError>
+    where
+        B: Body + 'static + Send,
+        B::Data: Send,
+        B::Error: Into<Box<dyn Error + Send + Sync>>,
+        I: hyper::rt::Read + hyper::rt::Write + Unpin + Send + 'static,
+    {
         let (mut sender, conn) = hyper::client::conn::http1::handshake(io)
             .await
             .map_err(|_| RequestError::UpstreamNotHttp)?;
@@ -54,6 +101,26 @@ pub enum RequestError {
     CannotConnect,
     #[error("Cannot serialize body")]
     CannotSerializeBody,
+    #[error("TLS handshake with upstream failed")]
+    TlsHandshake,
+}
+
+pub fn crypto_provider() -> Arc<CryptoProvider> {
+    Arc::new(ring::default_provider())
+}
+
+/// Verifies servers against the public roots plus any in `extra`.
+pub fn tls_client_config(extra: &RootCertStore) -> Arc<ClientConfig> {
+    let mut roots = RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+    roots.roots.extend(extra.roots.iter().cloned());
+    let config = ClientConfig::builder_with_provider(crypto_provider())
+        .with_safe_default_protocol_versions()
+        .expect("ring supports the default protocol versions")
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    Arc::new(config)
 }
 
 #[derive(Error, Debug, PartialEq)]
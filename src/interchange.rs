@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use serde_derive::{Deserialize, Serialize};
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -5,7 +7,78 @@ pub enum Command {
     CreateInstance,
     InstallMock {
         instance: InstanceId,
-        mock: MockRule,
+        mock: Box<MockRule>,
+    },
+    DeleteMock {
+        instance: InstanceId,
+        mock: MockId,
+    },
+    ReceivedRequests {
+        instance: InstanceId,
+    },
+    MockHits {
+        instance: InstanceId,
+        mock: MockId,
+    },
+    ConfigureCors {
+        instance: InstanceId,
+        cors: CorsPolicy,
+    },
+    /// Removes every installed mock, keeping the recorded requests.
+    ResetMocks {
+        instance: InstanceId,
+    },
+    DeleteInstance {
+        instance: InstanceId,
+    },
+    ScenarioStates {
+        instance: InstanceId,
+    },
+    /// Moves every scenario back to [`SCENARIO_STARTED`].
+    ResetScenarios {
+        instance: InstanceId,
+    },
+    /// The mock rules recorded from proxied traffic, ready to be installed
+    /// again for playback.
+    RecordedMocks {
+        instance: InstanceId,
+    },
+}
+
+#[cfg(feature = "server")]
+impl Command {
+    /// The instance the command acts on, `None` for `CreateInstance`.
+    pub(crate) fn instance(&self) -> Option<&InstanceId> {
+        match self {
+            Command::CreateInstance => None,
+            Command::InstallMock { instance, .. }
+            | Command::DeleteMock { instance, .. }
+            | Command::ReceivedRequests { instance }
+            | Command::MockHits { instance, .. }
+            | Command::ConfigureCors { instance, .. }
+            | Command::ResetMocks { instance }
+            | Command::DeleteInstance { instance }
+            | Command::ScenarioStates { instance }
+            | Command::ResetScenarios { instance }
+            | Command::RecordedMocks { instance } => Some(instance),
+        }
+    }
+}
+
+/// How the mock port answers cross-origin requests for an instance. Mocks
+/// matching an `OPTIONS` request always take over the preflight response.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub enum CorsPolicy {
+    /// Allow every origin, method and header.
+    #[default]
+    Permissive,
+    /// Add no CORS headers; preflights are handled like any other request.
+    Disabled,
+    /// Only allow the listed origins (`*` for any), methods and headers.
+    Restricted {
+        allowed_origins: Vec<String>,
+        allowed_methods: Vec<String>,
+        allowed_headers: Vec<String>,
     },
 }
 
@@ -16,40 +89,388 @@ pub struct InstanceResponse {
 }
 
 #[derive(Serialize, Deserialize, Debug)]
-pub struct InstallResponse;
+pub struct InstallResponse {
+    pub id: MockId,
+}
+
+/// The response to commands that only need acknowledging.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EmptyResponse;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub enum InstallError {
     InstanceNotFound,
+    InvalidMockRule(String),
+    MockNotFound,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    pub query: Option<String>,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// Segments captured by a path template, e.g. `("id", "123")` for
+    /// `/users/{id}` matching `/users/123`.
+    pub path_params: Vec<(String, String)>,
+    /// The mock that answered the request, `None` if nothing matched.
+    #[serde(default)]
+    pub matched: Option<MockId>,
+    /// For unmatched requests, the installed mocks ranked by how closely
+    /// they matched, closest first.
+    #[serde(default)]
+    pub near_misses: Vec<NearMiss>,
+}
+
+impl RecordedRequest {
+    /// The installed mock that came closest to matching this request.
+    pub fn closest_mock(&self) -> Option<&NearMiss> {
+        self.near_misses.first()
+    }
+
+    pub fn path_param(&self, name: &str) -> Option<&str> {
+        self.path_params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// The body of the 404 returned when no mock matched a request.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct UnmatchedReport {
+    pub method: String,
+    pub path: String,
+    pub near_misses: Vec<NearMiss>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NearMiss {
+    pub mock: MockId,
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl std::fmt::Display for NearMiss {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "mock {} differed in ", self.mock)?;
+        for (index, mismatch) in self.mismatches.iter().enumerate() {
+            if index > 0 {
+                f.write_str("; ")?;
+            }
+            write!(f, "{mismatch}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A field in which a mock rule differed from a request.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Mismatch {
+    pub field: String,
+    pub expected: String,
+    pub actual: String,
+}
+
+#[cfg(feature = "server")]
+impl Mismatch {
+    pub(crate) fn new(
+        field: impl Into<String>,
+        expected: impl Into<String>,
+        actual: impl Into<String>,
+    ) -> Self {
+        Self {
+            field: field.into(),
+            expected: expected.into(),
+            actual: actual.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: expected {}, got {}",
+            self.field, self.expected, self.actual
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct InstanceId(pub(crate) String);
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MockId(pub(crate) String);
+
+impl std::fmt::Display for MockId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct MockRule {
     pub when: WhenRules,
     pub then: ThenState,
+    /// Stop matching after answering this many requests.
+    #[serde(default)]
+    pub times: Option<u32>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct WhenRules {
-    pub match_path: String,
+    pub match_path: PathMatcher,
     pub form_data: Vec<(String, String)>,
     pub method: Option<Method>,
+    #[serde(default)]
+    pub headers: Vec<(String, String)>,
+    #[serde(default)]
+    pub query: Vec<(String, QueryValue)>,
+    /// Reject requests carrying query parameters not named in `query`.
+    #[serde(default)]
+    pub exact_query: bool,
+    #[serde(default)]
+    pub json_body: Option<JsonBody>,
+    #[serde(default)]
+    pub json_predicates: Vec<JsonPredicate>,
+    /// Only match while the named scenario is in the given state.
+    #[serde(default)]
+    pub scenario: Option<Scenario>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// The state every scenario starts in.
+pub const SCENARIO_STARTED: &str = "Started";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Scenario {
+    pub name: String,
+    pub state: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", content = "value")]
+pub enum PathMatcher {
+    /// The request path must equal this string.
+    Exact(String),
+    /// The whole request path must match this regular expression.
+    Regex(String),
+    /// The request path must match this glob: `*` matches within a segment,
+    /// `**` across segments.
+    Glob(String),
+    /// An OpenAPI style template such as `/users/{id}/orders/{orderId}`; each
+    /// `{name}` segment matches any single segment and is captured.
+    Template(String),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum QueryValue {
+    /// The parameter must have exactly this value, e.g. `?q=foo`.
+    Exact(String),
+    /// The parameter must be present without a value, e.g. `?debug` or `?debug=`.
+    Present,
+    /// The parameter must be present, whatever its value.
+    Any,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum JsonBody {
+    /// The request body must be structurally equal to this document.
+    Exact(serde_json::Value),
+    /// The request body must contain this document: objects may carry extra
+    /// keys and arrays extra elements.
+    Partial(serde_json::Value),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct JsonPredicate {
+    pub selector: JsonSelector,
+    pub condition: JsonCondition,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum JsonSelector {
+    /// An RFC 6901 JSON Pointer, e.g. `/order/items/0/sku`.
+    Pointer(String),
+    /// A single-value JSONPath expression, e.g. `$.order.items[0].sku`.
+    Path(String),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum JsonCondition {
+    /// The selected value must equal this value.
+    Equals(serde_json::Value),
+    /// A value must exist at the selected location.
+    Exists,
+    /// The selected value must match this regular expression. Non-string
+    /// values are matched against their JSON representation.
+    Matches(String),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct ThenState {
     pub status: u16,
     pub headers: Vec<(String, String)>,
     pub body: Vec<u8>,
+    /// How long to wait before answering.
+    #[serde(default)]
+    pub delay: Option<Delay>,
+    /// Sends the body in small chunks over time instead of all at once.
+    #[serde(default)]
+    pub dribble: Option<Dribble>,
+    /// Misbehave at the connection level instead of answering normally.
+    #[serde(default)]
+    pub fault: Option<Fault>,
+    /// The state to move the rule's scenario to once it has answered.
+    #[serde(default)]
+    pub transition_to: Option<String>,
+    /// Responses used in turn after the first one above.
+    #[serde(default)]
+    pub responses: Vec<CannedResponse>,
+    /// What happens once every response has been used.
+    #[serde(default)]
+    pub sequence: SequencePolicy,
+    /// Fill `{{...}}` placeholders in the body and header values from the
+    /// request, e.g. `{{path.id}}` or `{{json.user.name}}`.
+    #[serde(default)]
+    pub templated: bool,
+    /// Forward the request to an upstream and answer with its response
+    /// instead of the status, headers and body above.
+    #[serde(default)]
+    pub passthrough: Option<Passthrough>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Passthrough {
+    /// Where to forward to, e.g. `http://localhost:8080`; a path here is
+    /// prefixed to the request's path.
+    pub upstream: String,
+    /// Headers set on the forwarded request, replacing any sent.
+    #[serde(default)]
+    pub request_headers: Vec<(String, String)>,
+    /// Headers removed from the forwarded request.
+    #[serde(default)]
+    pub remove_request_headers: Vec<String>,
+    /// Headers set on the upstream's response, replacing any it sent.
+    #[serde(default)]
+    pub response_headers: Vec<(String, String)>,
+}
+
+impl Passthrough {
+    pub fn to(upstream: &str) -> Self {
+        Self {
+            upstream: String::from(upstream),
+            request_headers: vec![],
+            remove_request_headers: vec![],
+            response_headers: vec![],
+        }
+    }
+
+    pub fn request_header(mut self, name: &str, value: &str) -> Self {
+        self.request_headers
+            .push((String::from(name), String::from(value)));
+        self
+    }
+
+    pub fn remove_request_header(mut self, name: &str) -> Self {
+        self.remove_request_headers.push(String::from(name));
+        self
+    }
+
+    pub fn response_header(mut self, name: &str, value: &str) -> Self {
+        self.response_headers
+            .push((String::from(name), String::from(value)));
+        self
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CannedResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub enum SequencePolicy {
+    /// Keep answering with the last response.
+    #[default]
+    RepeatLast,
+    /// Start again from the first response.
+    Cycle,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Fault {
+    /// Reset the TCP connection without replying.
+    ConnectionReset,
+    /// Close the connection without replying.
+    EmptyReply,
+    /// Send bytes that aren't HTTP, then close the connection.
+    Garbage,
+    /// Send the status line and headers, then never send the body.
+    HeadersThenStall,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Delay {
+    Fixed(Duration),
+    /// Picked uniformly between `min` and `max` for every response.
+    Uniform {
+        min: Duration,
+        max: Duration,
+    },
+    /// Picked from a log-normal distribution around `median`, which gives the
+    /// long tail real latencies have, then clamped between `min` and `max`.
+    LogNormal {
+        median: Duration,
+        sigma: f64,
+        min: Duration,
+        max: Duration,
+    },
+}
+
+impl From<Duration> for Delay {
+    fn from(delay: Duration) -> Self {
+        Delay::Fixed(delay)
+    }
+}
+
+/// Sends `chunk_size` bytes of the body every `interval`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Dribble {
+    pub chunk_size: usize,
+    pub interval: Duration,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum Method {
     GET,
     POST,
     DELETE,
     PUT,
+    PATCH,
+    HEAD,
+    OPTIONS,
+    CONNECT,
+    TRACE,
+    /// An extension method such as `PURGE`; compared case-sensitively.
+    Custom(String),
+}
+
+impl Method {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Method::GET => "GET",
+            Method::POST => "POST",
+            Method::DELETE => "DELETE",
+            Method::PUT => "PUT",
+            Method::PATCH => "PATCH",
+            Method::HEAD => "HEAD",
+            Method::OPTIONS => "OPTIONS",
+            Method::CONNECT => "CONNECT",
+            Method::TRACE => "TRACE",
+            Method::Custom(method) => method,
+        }
+    }
 }
@@ -5,5 +5,5 @@ pub mod server;
 pub mod client;
 #[cfg(feature = "client")]
 pub(crate) mod network_client;
-#[cfg(not(target_arch = "wasm32"))]
+#[cfg(all(not(target_arch = "wasm32"), any(feature = "server", feature = "client")))]
 mod hyper_helpers;
@@ -1,9 +1,31 @@
 use thiserror::Error;
-pub struct NetworkClient;
+
+#[derive(Debug, Clone, Default)]
+pub struct NetworkClient {
+    /// Verifies `https://` control planes; public roots only when unset.
+    #[cfg(not(target_arch = "wasm32"))]
+    tls: Option<std::sync::Arc<rustls::ClientConfig>>,
+}
 
 #[cfg(all(feature = "client", not(target_arch = "wasm32")))]
 impl NetworkClient {
+    /// Also trusts servers whose certificates chain to `ca_pem`.
+    pub fn trusting(ca_pem: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        use rustls::pki_types::{pem::PemObject, CertificateDer};
+        let mut roots = rustls::RootCertStore::empty();
+        for cert in CertificateDer::pem_slice_iter(ca_pem.as_bytes()) {
+            roots.add(cert?)?;
+        }
+        if roots.is_empty() {
+            return Err("No certificates in CA PEM".into());
+        }
+        Ok(Self {
+            tls: Some(crate::hyper_helpers::tls_client_config(&roots)),
+        })
+    }
+
     pub async fn send<T, U, E>(
+        &self,
         control_plane_url: &str,
         message: &T,
     ) -> Result<U, ClientNetworkError<E>>
@@ -20,7 +42,8 @@ impl NetworkClient {
         let host = url
             .host()
             .ok_or(ClientNetworkError::FailedToConnectToMockServer)?;
-        let port = url.port_u16().unwrap_or(80);
+        let https = url.scheme_str() == Some("https");
+        let port = url.port_u16().unwrap_or(if https { 443 } else { 80 });
         let address = format!("{}:{}", host, port);
 
         let request = hyper::Request::builder()
@@ -31,9 +54,15 @@ impl NetworkClient {
             .map_err(|_| ClientNetworkError::FailedBuildRequest)?;
 
 
-        let response = HyperHelpers::send(&address, request)
-            .await
-            .map_err(|_| ClientNetworkError::FailedToConnectToMockServer)?;
+        let response = if https {
+            let tls = self.tls.clone().unwrap_or_else(|| {
+                crate::hyper_helpers::tls_client_config(&rustls::RootCertStore::empty())
+            });
+            HyperHelpers::send_tls(&tls, &address, request).await
+        } else {
+            HyperHelpers::send(&address, request).await
+        }
+        .map_err(|_| ClientNetworkError::FailedToConnectToMockServer)?;
 
         if response.status().is_success() {
             response
@@ -52,6 +81,7 @@ impl NetworkClient {
 #[cfg(all(feature = "client", any(target_arch = "wasm32", rust_analyzer)))]
 impl NetworkClient {
     pub async fn send<T, U, E>(
+        &self,
         control_plane_url: &str,
         message: &T,
     ) -> Result<U, ClientNetworkError<E>>
@@ -1,24 +1,52 @@
-use std::{collections::HashMap, convert::Infallible, net::SocketAddr, sync::Arc};
+mod cors;
+mod fault;
+mod intercept;
+mod json;
+mod latency;
+mod passthrough;
+mod path;
+mod record;
+mod routing;
+mod template;
+mod tls;
+mod tunnel;
+
+pub use intercept::{CertificateAuthority, Interception};
+pub use routing::INSTANCE_HEADER;
+pub use tls::TlsIdentity;
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    convert::Infallible,
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use crate::{
-    hyper_helpers::ResponseExt,
+    hyper_helpers::{HyperHelpers, ResponseExt},
     interchange::{
-        Command, InstallError, InstallResponse, InstanceId, InstanceResponse, Method, MockRule,
+        CannedResponse, Command, CorsPolicy, EmptyResponse, Fault, InstallError, InstallResponse,
+        InstanceId, InstanceResponse, JsonBody, Method, Mismatch, MockId, MockRule, NearMiss,
+        Passthrough, PathMatcher, QueryValue, RecordedRequest, Scenario, SequencePolicy, ThenState,
+        UnmatchedReport, SCENARIO_STARTED,
     },
 };
 use eyre::{eyre, WrapErr};
-use http_body_util::{BodyExt, Full};
+use fault::{FaultTrigger, FaultyStream};
+use http_body_util::{combinators::BoxBody, BodyExt, Full};
 use hyper::{
     body::{Body, Bytes},
     server::conn::http1,
-    Request, Response,
+    service::service_fn,
+    HeaderMap, Request, Response,
 };
 use hyper_util::{rt::TokioIo, service::TowerToHyperService};
 use thiserror::Error;
-use tokio::{net::TcpListener, sync::RwLock};
+use tokio::{net::TcpListener, sync::RwLock, task::AbortHandle};
 use tower::ServiceBuilder;
 use tower_http::cors::CorsLayer;
-use tracing::{info, trace};
+use tracing::{error, info, trace};
 
 #[tracing::instrument(skip(state, req), level = "trace", fields(http.method=%req.method(), http.uri=%req.uri()))]
 pub async fn control_handler<T>(
@@ -35,55 +63,202 @@ where
     }
 }
 
-#[tracing::instrument(skip(state, mode, req), level = "info", fields(http.method=%req.method(), http.uri=%req.uri()))]
+#[tracing::instrument(skip(state, listener, faults, req), level = "info", fields(http.method=%req.method(), http.uri=%req.uri()))]
 async fn mock_handler<T>(
     req: Request<T>,
     state: SequentialState,
-    mode: Mode,
-) -> Result<Response<Full<Bytes>>, Infallible>
+    listener: Option<InstanceId>,
+    faults: FaultTrigger,
+) -> Result<Response<MockBody>, Infallible>
 where
     T: Body + std::fmt::Debug,
     T::Error: std::fmt::Debug,
 {
-    let req = UnpackedRequest::from_request(req).await;
-    {
-        let instance = state.instance.read().await;
-
-        if let Some((_, mocks)) = instance.as_ref() {
-            for mock in mocks {
-                if mock.matches(&req) {
-                    info!("Found matching mock rule");
-                    let builder = Response::builder().status(mock.then.status);
-                    let builder = mock
-                        .then
-                        .headers
-                        .iter()
-                        .fold(builder, |builder, (k, v)| builder.header(k, v));
-                    return Ok(builder
-                        .body(Full::new(Bytes::from(mock.then.body.clone())))
-                        .unwrap());
+    let mut req = UnpackedRequest::from_request(req).await;
+    // An instance's own listener only ever serves that instance.
+    let instance_id = match listener {
+        Some(id) => Some(id),
+        None if state.isolated => routing::take_instance_id(&mut req),
+        None => None,
+    };
+    let (cors, near_misses) = 'lookup: {
+        let mut instances = state.instances.write().await;
+        let Some(instance) = state.find_instance(&mut instances, instance_id.as_ref()) else {
+            break 'lookup (CorsPolicy::default(), vec![]);
+        };
+        instance.last_used = Instant::now();
+        if let Some(index) = instance
+            .mocks
+            .iter()
+            .position(|m| instance.matches(m, &req))
+        {
+            let installed = &mut instance.mocks[index];
+            installed.uses += 1;
+            let mock = &installed.rule;
+            req.path_params = path::captures(&mock.when.match_path, req.uri.path());
+            info!(mock = %installed.id, path_params = ?req.path_params, "Found matching mock rule");
+            let answer = match &mock.then.passthrough {
+                Some(passthrough) => Answer::Passthrough(passthrough.clone()),
+                None => {
+                    let canned = canned_response(&mock.then, installed.uses - 1);
+                    let canned = if mock.then.templated {
+                        template::render_response(canned, &req, installed.uses)
+                    } else {
+                        canned
+                    };
+                    Answer::Canned(mock_response(canned, &mock.then, &faults))
                 }
+            };
+            if let (Some(scenario), Some(next)) = (&mock.when.scenario, &mock.then.transition_to) {
+                info!(scenario = %scenario.name, state = %next, "Scenario transitioned");
+                instance
+                    .scenarios
+                    .insert(scenario.name.clone(), next.clone());
+            }
+            let delay = mock.then.delay.as_ref().map(latency::sample);
+            let cors = instance.cors.clone();
+            instance
+                .requests
+                .push(req.to_recorded(Some(installed.id.clone())));
+            // Other requests shouldn't wait on this one's delay.
+            drop(instances);
+            if let Some(delay) = delay {
+                tokio::time::sleep(delay).await;
+            }
+            let mut response = match answer {
+                Answer::Canned(response) => response,
+                Answer::Passthrough(passthrough) => passthrough::response(&req, &passthrough)
+                    .await?
+                    .map(BodyExt::boxed),
+            };
+            // A mock matching a preflight takes it over entirely.
+            if !cors::is_preflight(&req) {
+                cors::apply(&cors, &req, &mut response);
             }
+            return Ok(response);
+        }
+        let near_misses = instance.near_misses(&req);
+        if let Some(closest) = near_misses.first() {
+            info!(%closest, "No mock rule matched");
+        }
+        let mut recorded = req.to_recorded(None);
+        recorded.near_misses = near_misses.clone();
+        instance.requests.push(recorded);
+        (instance.cors.clone(), near_misses)
+    };
+
+    let response = unmatched_response(&req, cors, &state, instance_id.as_ref(), near_misses).await;
+    response.map(|response| response.map(BodyExt::boxed))
+}
+
+/// Mock responses are usually sent whole, but may be dribbled out.
+type MockBody = BoxBody<Bytes, Infallible>;
+
+/// How a matched mock answers.
+enum Answer {
+    Canned(Response<MockBody>),
+    Passthrough(Passthrough),
+}
+
+fn mock_response(
+    canned: CannedResponse,
+    then: &ThenState,
+    faults: &FaultTrigger,
+) -> Response<MockBody> {
+    let builder = Response::builder().status(canned.status);
+    let builder = canned
+        .headers
+        .iter()
+        .fold(builder, |builder, (k, v)| builder.header(k, v));
+    let body = Bytes::from(canned.body);
+    let body = match (&then.fault, &then.dribble) {
+        (Some(Fault::HeadersThenStall), _) => fault::StalledBody.boxed(),
+        (Some(fault), _) => {
+            info!(?fault, "Injecting fault");
+            faults.trigger(fault.clone());
+            Full::new(body).boxed()
+        }
+        (None, Some(dribble)) => latency::DribbleBody::new(body, dribble).boxed(),
+        (None, None) => Full::new(body).boxed(),
+    };
+    // Header names and values come from the rule, so they may not be valid.
+    builder.body(body).unwrap_or_else(|err| {
+        error!(?err, "Cannot build mock response");
+        Response::builder()
+            .status(500)
+            .body(Full::new(Bytes::new()).boxed())
+            .unwrap()
+    })
+}
+
+async fn unmatched_response(
+    req: &UnpackedRequest,
+    cors: CorsPolicy,
+    state: &SequentialState,
+    instance_id: Option<&InstanceId>,
+    near_misses: Vec<NearMiss>,
+) -> Result<Response<Full<Bytes>>, Infallible> {
+    if cors::is_preflight(req) {
+        if let Some(response) = cors::preflight_response(&cors, req) {
+            return Ok(response);
         }
     }
 
-    match mode {
-        Mode::Proxy => match request_from_proxy(req).await {
-            Ok(res) => proxy_response_to_response(res)
-                .await
-                .map(|res| {
-                    info!("Proxying response");
-                    res
-                })
-                .or_else(|e| e.to_response()),
+    let report = UnmatchedReport {
+        method: req.method.to_string(),
+        path: req.uri.path().to_string(),
+        near_misses,
+    };
+    let mut response = match state.mode {
+        Mode::Proxy => proxied_response(req, state)
+            .await
+            .or_else(|e| e.to_response()),
+        Mode::Record => match proxied_response(req, state).await {
+            Ok(response) => {
+                record::store(state, instance_id, req, &response).await;
+                Ok(response)
+            }
             Err(e) => e.to_response(),
         },
-        Mode::Mock => respond(404, "Not Found"),
-    }
+        Mode::Mock => report_response(404, &report),
+        Mode::Playback => {
+            error!(method = %report.method, path = %report.path, "No recording for request");
+            report_response(500, &report)
+        }
+    }?;
+    cors::apply(&cors, req, &mut response);
+    Ok(response)
+}
+
+/// Forwards to the fixed upstream when reverse proxying or intercepting,
+/// otherwise to the host the request names.
+async fn proxied_response(
+    req: &UnpackedRequest,
+    state: &SequentialState,
+) -> Result<Response<Full<Bytes>>, ProxyError> {
+    let tls = state.interception.as_deref().map(Interception::upstream);
+    let res = match &state.upstream {
+        Some(upstream) => passthrough::request_upstream(req, upstream, tls).await?,
+        None => request_from_proxy(req).await?,
+    };
+    let response = proxy_response_to_response(res).await?;
+    info!("Proxying response");
+    Ok(response)
+}
+
+fn report_response(
+    status: u16,
+    report: &UnmatchedReport,
+) -> Result<Response<Full<Bytes>>, Infallible> {
+    Ok(Response::builder()
+        .status(status)
+        .header("content-type", "application/json")
+        .body(Full::new(Bytes::from(serde_json::to_vec(report).unwrap())))
+        .unwrap())
 }
 
 async fn request_from_proxy(
-    req: UnpackedRequest,
+    req: &UnpackedRequest,
 ) -> Result<Response<hyper::body::Incoming>, ProxyError> {
     let url = req
         .headers
@@ -94,38 +269,45 @@ async fn request_from_proxy(
     let host = url.host().ok_or(ProxyError::BadHostHeader)?;
     let port = url.port_u16().unwrap_or(80);
     let address = format!("{}:{}", host, port);
-    let mut builder = Request::builder().method(req.method).uri(req.uri.path());
+    let path = req.uri.path_and_query().map_or("/", |p| p.as_str());
+    send_upstream(req, &address, path, req.headers.clone(), None).await
+}
+
+async fn send_upstream(
+    req: &UnpackedRequest,
+    address: &str,
+    uri: &str,
+    headers: HeaderMap,
+    tls: Option<&Arc<rustls::ClientConfig>>,
+) -> Result<Response<hyper::body::Incoming>, ProxyError> {
+    let mut builder = Request::builder().method(req.method.clone()).uri(uri);
 
-    if let Some(headers) = builder.headers_mut() {
-        *headers = req.headers;
+    if let Some(builder_headers) = builder.headers_mut() {
+        *builder_headers = headers;
     }
     let proxied_req = builder
-        .body(Full::new(req.body))
+        .body(Full::new(req.body.clone()))
         .map_err(|_| ProxyError::CannotReadRequestBody)?;
 
-    let response = crate::hyper_helpers::HyperHelpers::send(&address, proxied_req)
-        .await
-        .map_err(|err| {
-            use crate::hyper_helpers::RequestError::*;
-            match err {
-                UpstreamNotHttp => ProxyError::UpstreamNotHttp,
-                UpstreamSendError => ProxyError::UpstreamSendError,
-                CannotConnect => ProxyError::UpstreamNotFound,
-                CannotSerializeBody => ProxyError::UpstreamSendError,
-            }
-        })?;
-
-    Ok(response)
+    match tls {
+        Some(tls) => HyperHelpers::send_tls(tls, address, proxied_req).await,
+        None => HyperHelpers::send(address, proxied_req).await,
+    }
+    .map_err(ProxyError::from)
 }
 
 #[derive(Debug, Error)]
 enum ProxyError {
     #[error("Bad host header")]
     BadHostHeader,
+    #[error("Bad passthrough upstream")]
+    BadUpstream,
     #[error("Upstream not found")]
     UpstreamNotFound,
     #[error("Upstream does not support HTTP")]
     UpstreamNotHttp,
+    #[error("Upstream TLS handshake failed")]
+    UpstreamTls,
     #[error("Cannot read request body")]
     CannotReadRequestBody,
     #[error("Upstream send error")]
@@ -140,8 +322,10 @@ impl ProxyError {
     fn to_response(&self) -> Result<Response<Full<Bytes>>, Infallible> {
         match self {
             ProxyError::BadHostHeader => respond(400, "Bad host header"),
+            ProxyError::BadUpstream => respond(502, "Bad passthrough upstream"),
             ProxyError::UpstreamNotFound => respond(502, "Upstream not found"),
             ProxyError::UpstreamNotHttp => respond(502, "Upstream not HTTP"),
+            ProxyError::UpstreamTls => respond(502, "Upstream TLS handshake failed"),
             ProxyError::CannotReadRequestBody => respond(502, "Cannot read request body"),
             ProxyError::UpstreamSendError => respond(502, "Upstream send error"),
             ProxyError::CannotReadResponseBody => respond(502, "Cannot read response body"),
@@ -152,6 +336,19 @@ impl ProxyError {
     }
 }
 
+impl From<crate::hyper_helpers::RequestError> for ProxyError {
+    fn from(err: crate::hyper_helpers::RequestError) -> Self {
+        use crate::hyper_helpers::RequestError::*;
+        match err {
+            UpstreamNotHttp => ProxyError::UpstreamNotHttp,
+            UpstreamSendError => ProxyError::UpstreamSendError,
+            CannotConnect => ProxyError::UpstreamNotFound,
+            CannotSerializeBody => ProxyError::UpstreamSendError,
+            TlsHandshake => ProxyError::UpstreamTls,
+        }
+    }
+}
+
 async fn proxy_response_to_response(
     res: Response<hyper::body::Incoming>,
 ) -> Result<Response<Full<Bytes>>, ProxyError> {
@@ -189,18 +386,40 @@ where
         }
     };
     trace!("Received Command: {command:?}");
+    if let Some(instance_id) = command.instance() {
+        state.touch(instance_id).await;
+    }
     match command {
         Command::CreateInstance => {
             let instance_id = InstanceId(uuid7::uuid7().to_string());
+            let mut instance = InstanceState::new();
+            let url = if state.instance_listeners {
+                let binding = match bind_socket(SocketAddr::from(([127, 0, 0, 1], 0))).await {
+                    Ok(binding) => binding,
+                    Err(err) => {
+                        info!(error=%err, "Cannot bind instance listener");
+                        return respond(500, "Cannot bind instance listener");
+                    }
+                };
+                let server = serve_mock(binding.listener, state.clone(), Some(instance_id.clone()));
+                instance.listener = Some(AbortOnDrop(tokio::spawn(server).abort_handle()));
+                state.origin(binding.port)
+            } else if state.isolated {
+                routing::instance_url(&state.origin(state.mock_port), &instance_id)
+            } else {
+                state.origin(state.mock_port)
+            };
             {
-                info!("attempt to lock: {:?}", state.instance.try_write());
-                let mut instance = state.instance.write().await;
-                *instance = Some((instance_id.clone(), vec![]));
+                let mut instances = state.instances.write().await;
+                if !state.isolated {
+                    instances.clear();
+                }
+                instances.insert(instance_id.clone(), instance);
             }
-            info!(instance=?instance_id, "Created instance");
+            info!(instance=?instance_id, %url, "Created instance");
             let instance_response = InstanceResponse {
                 instance: instance_id,
-                url: format!("http://localhost:{}", state.mock_port),
+                url,
             };
             respond(200, serde_json::to_string(&instance_response).unwrap())
         }
@@ -208,23 +427,167 @@ where
             mock,
             instance: instance_id,
         } => {
-            if instance_id != state.instance.read().await.as_ref().unwrap().0 {
-                let body = serde_json::to_string(&InstallError::InstanceNotFound).unwrap();
-                return respond(400, body);
-            }
+            let mut instances = state.instances.write().await;
+            let Some(instance) = instances.get_mut(&instance_id) else {
+                return instance_not_found();
+            };
 
-            let mut instance = state.instance.write().await;
-            info!("Mock installed: {:?}", mock.when);
-            if let Some((_, mocks)) = instance.as_mut() {
-                mocks.push(mock);
-                mocks.sort_by_key(|m| m.priority());
-                mocks.reverse();
+            let patterns = match mock.compile() {
+                Ok(patterns) => patterns,
+                Err(reason) => {
+                    info!(%reason, "Rejected invalid mock rule");
+                    let body =
+                        serde_json::to_string(&InstallError::InvalidMockRule(reason)).unwrap();
+                    return respond(400, body);
+                }
+            };
+
+            let id = MockId(uuid7::uuid7().to_string());
+            info!(mock = %id, "Mock installed: {:?}", mock.when);
+            instance.mocks.push(InstalledMock {
+                id: id.clone(),
+                rule: *mock,
+                patterns,
+                uses: 0,
+                recorded: None,
+            });
+            instance.mocks.sort_by_key(|m| m.rule.priority());
+            instance.mocks.reverse();
+            respond(200, serde_json::to_string(&InstallResponse { id }).unwrap())
+        }
+        Command::DeleteMock {
+            instance: instance_id,
+            mock: mock_id,
+        } => {
+            let mut instances = state.instances.write().await;
+            let Some(instance) = instances.get_mut(&instance_id) else {
+                return instance_not_found();
+            };
+            match instance.mocks.iter().position(|m| m.id == mock_id) {
+                Some(index) => {
+                    instance.mocks.remove(index);
+                    info!(mock = %mock_id, "Mock deleted");
+                    respond(200, serde_json::to_string(&EmptyResponse).unwrap())
+                }
+                None => {
+                    let body = serde_json::to_string(&InstallError::MockNotFound).unwrap();
+                    respond(404, body)
+                }
+            }
+        }
+        Command::ConfigureCors {
+            instance: instance_id,
+            cors,
+        } => {
+            let mut instances = state.instances.write().await;
+            match instances.get_mut(&instance_id) {
+                Some(instance) => {
+                    info!(?cors, "CORS configured");
+                    instance.cors = cors;
+                    respond(200, serde_json::to_string(&EmptyResponse).unwrap())
+                }
+                None => instance_not_found(),
+            }
+        }
+        Command::ResetMocks {
+            instance: instance_id,
+        } => {
+            let mut instances = state.instances.write().await;
+            match instances.get_mut(&instance_id) {
+                Some(instance) => {
+                    instance.mocks.clear();
+                    info!(instance=?instance_id, "Mocks reset");
+                    respond(200, serde_json::to_string(&EmptyResponse).unwrap())
+                }
+                None => instance_not_found(),
+            }
+        }
+        Command::DeleteInstance {
+            instance: instance_id,
+        } => {
+            // Dropping the instance also stops its listener.
+            match state.instances.write().await.remove(&instance_id) {
+                Some(_) => {
+                    info!(instance=?instance_id, "Deleted instance");
+                    respond(200, serde_json::to_string(&EmptyResponse).unwrap())
+                }
+                None => instance_not_found(),
+            }
+        }
+        Command::ReceivedRequests {
+            instance: instance_id,
+        } => {
+            let instances = state.instances.read().await;
+            match instances.get(&instance_id) {
+                Some(instance) => respond(200, serde_json::to_string(&instance.requests).unwrap()),
+                None => instance_not_found(),
+            }
+        }
+        Command::MockHits {
+            instance: instance_id,
+            mock: mock_id,
+        } => {
+            let instances = state.instances.read().await;
+            let Some(instance) = instances.get(&instance_id) else {
+                return instance_not_found();
+            };
+            let hits = instance
+                .requests
+                .iter()
+                .filter(|request| request.matched.as_ref() == Some(&mock_id))
+                .collect::<Vec<_>>();
+            respond(200, serde_json::to_string(&hits).unwrap())
+        }
+        Command::ScenarioStates {
+            instance: instance_id,
+        } => {
+            let instances = state.instances.read().await;
+            match instances.get(&instance_id) {
+                Some(instance) => respond(
+                    200,
+                    serde_json::to_string(&instance.scenario_states()).unwrap(),
+                ),
+                None => instance_not_found(),
+            }
+        }
+        Command::RecordedMocks {
+            instance: instance_id,
+        } => {
+            let instances = state.instances.read().await;
+            match instances.get(&instance_id) {
+                Some(instance) => {
+                    let recorded = instance
+                        .mocks
+                        .iter()
+                        .filter(|m| m.recorded.is_some())
+                        .map(|m| &m.rule)
+                        .collect::<Vec<_>>();
+                    respond(200, serde_json::to_string(&recorded).unwrap())
+                }
+                None => instance_not_found(),
+            }
+        }
+        Command::ResetScenarios {
+            instance: instance_id,
+        } => {
+            let mut instances = state.instances.write().await;
+            match instances.get_mut(&instance_id) {
+                Some(instance) => {
+                    instance.scenarios.clear();
+                    info!(instance=?instance_id, "Scenarios reset");
+                    respond(200, serde_json::to_string(&EmptyResponse).unwrap())
+                }
+                None => instance_not_found(),
             }
-            respond(200, serde_json::to_string(&InstallResponse).unwrap())
         }
     }
 }
 
+fn instance_not_found() -> Result<Response<Full<Bytes>>, Infallible> {
+    let body = serde_json::to_string(&InstallError::InstanceNotFound).unwrap();
+    respond(400, body)
+}
+
 async fn parse_command<T>(req: Request<T>) -> Result<Command, eyre::Report>
 where
     T: Body,
@@ -244,7 +607,6 @@ where
 fn respond(status: u16, body: impl Into<Bytes>) -> Result<Response<Full<Bytes>>, Infallible> {
     Ok(Response::builder()
         .status(status)
-        .header("Access-Control-Allow-Origin", "*")
         .body(Full::new(body.into()))
         .unwrap())
 }
@@ -266,111 +628,491 @@ pub async fn run_controlplane(
     listener: TcpListener,
     state: SequentialState,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let _eviction = state.idle_ttl.map(|ttl| {
+        AbortOnDrop(tokio::spawn(evict_idle_instances(state.clone(), ttl)).abort_handle())
+    });
     loop {
         let state = state.clone();
         let (stream, _) = listener.accept().await?;
-        let io = TokioIo::new(stream);
+        let tls = state.tls.clone();
 
         tokio::task::spawn(async move {
             let service = ServiceBuilder::new()
                 .layer(CorsLayer::permissive())
                 .service_fn(move |req| control_handler(req, state.clone()));
-            if let Err(err) = http1::Builder::new()
-                .serve_connection(io, TowerToHyperService::new(service))
-                .await
-            {
+            let service = TowerToHyperService::new(service);
+            let builder = http1::Builder::new();
+            let result = match tls {
+                Some(tls) => match tls.acceptor().accept(stream).await {
+                    Ok(stream) => {
+                        builder
+                            .serve_connection(TokioIo::new(stream), service)
+                            .await
+                    }
+                    Err(err) => {
+                        trace!(?err, "TLS handshake failed");
+                        return;
+                    }
+                },
+                None => {
+                    builder
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await
+                }
+            };
+            if let Err(err) = result {
                 println!("Error serving connection: {:?}", err);
             }
         });
     }
 }
 
+async fn evict_idle_instances(state: SequentialState, ttl: Duration) {
+    let period = (ttl / 2).clamp(Duration::from_millis(10), Duration::from_secs(1));
+    let mut interval = tokio::time::interval(period);
+    loop {
+        interval.tick().await;
+        state.instances.write().await.retain(|id, instance| {
+            let idle = instance.last_used.elapsed() >= ttl;
+            if idle {
+                info!(instance=?id, "Evicted idle instance");
+            }
+            !idle
+        });
+    }
+}
+
+/// Serves mock requests in the mode `state` was configured with.
+pub async fn run_mockplane(
+    listener: TcpListener,
+    state: SequentialState,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    serve_mock(listener, state, None).await
+}
+
+/// Serves mock requests in `mode`. Instance listeners opened through the
+/// control plane use the mode of the state it was started with instead.
+#[deprecated(note = "configure the mode with `SequentialState::with_mode` and use `run_mockplane`")]
 pub async fn run_mock(
     listener: TcpListener,
     state: SequentialState,
     mode: Mode,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    run_mockplane(listener, state.with_mode(mode)).await
+}
+
+async fn serve_mock(
+    listener: TcpListener,
+    state: SequentialState,
+    instance: Option<InstanceId>,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     loop {
+        let instance = instance.clone();
         let state = state.clone();
         let (stream, _) = listener.accept().await?;
-        let io = TokioIo::new(stream);
+        let faults = FaultTrigger::default();
+        let stream = FaultyStream::new(stream, faults.clone());
+        let tls = state.tls.clone();
 
         tokio::task::spawn(async move {
-            let service = ServiceBuilder::new()
-                .layer(CorsLayer::permissive())
-                .service_fn(move |req| mock_handler(req, state.clone(), mode));
-            if let Err(err) = http1::Builder::new()
-                .serve_connection(io, TowerToHyperService::new(service))
-                .await
-            {
+            // CORS is applied per instance by the handler so mocks can answer
+            // preflights themselves.
+            let service = service_fn(move |req: Request<hyper::body::Incoming>| {
+                let (state, instance, faults) = (state.clone(), instance.clone(), faults.clone());
+                async move {
+                    if req.method() == hyper::Method::CONNECT {
+                        if let Some(interception) = state.interception.clone() {
+                            return intercept::connect(req, state, instance, interception, faults)
+                                .await;
+                        }
+                        if state.mode == Mode::Proxy {
+                            return tunnel::connect(req).await;
+                        }
+                    }
+                    mock_handler(req, state, instance, faults).await
+                }
+            });
+            let builder = http1::Builder::new();
+            let result = match tls {
+                Some(tls) => match tls.acceptor().accept(stream).await {
+                    Ok(stream) => {
+                        builder
+                            .serve_connection(TokioIo::new(stream), service)
+                            .with_upgrades()
+                            .await
+                    }
+                    Err(err) => {
+                        trace!(?err, "TLS handshake failed");
+                        return;
+                    }
+                },
+                None => {
+                    builder
+                        .serve_connection(TokioIo::new(stream), service)
+                        .with_upgrades()
+                        .await
+                }
+            };
+            if let Err(err) = result {
                 println!("Error serving connection: {:?}", err);
             }
         });
     }
 }
 
-type Instance = Arc<RwLock<Option<(InstanceId, Vec<MockRule>)>>>;
+type Instances = Arc<RwLock<HashMap<InstanceId, InstanceState>>>;
+
+#[derive(Debug)]
+struct InstanceState {
+    mocks: Vec<InstalledMock>,
+    requests: Vec<RecordedRequest>,
+    cors: CorsPolicy,
+    /// Scenarios that have moved out of [`SCENARIO_STARTED`].
+    scenarios: HashMap<String, String>,
+    /// The instance's own mock listener, stopped when the instance goes away.
+    listener: Option<AbortOnDrop>,
+    last_used: Instant,
+}
+
+/// Aborts a spawned task once its owner goes away.
+#[derive(Debug)]
+struct AbortOnDrop(AbortHandle);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+impl InstanceState {
+    /// Every installed mock ranked by how few fields it differed in, ties
+    /// keeping the matching order.
+    fn near_misses(&self, req: &UnpackedRequest) -> Vec<NearMiss> {
+        let mut near_misses = self
+            .mocks
+            .iter()
+            .map(|mock| NearMiss {
+                mock: mock.id.clone(),
+                mismatches: self.mismatches(mock, req),
+            })
+            .collect::<Vec<_>>();
+        near_misses.sort_by_key(|near_miss| near_miss.mismatches.len());
+        near_misses
+    }
+
+    fn matches(&self, mock: &InstalledMock, req: &UnpackedRequest) -> bool {
+        trace!(?req, "Checking if request matches");
+        self.mismatches(mock, req).is_empty()
+    }
+
+    /// How a mock differs from the request, including whether it is used up
+    /// and whether its scenario is in the required state.
+    fn mismatches(&self, mock: &InstalledMock, req: &UnpackedRequest) -> Vec<Mismatch> {
+        let rule = &mock.rule;
+        let mut mismatches = rule.mismatches(&mock.patterns, req);
+        if let Some(times) = rule.times.filter(|times| mock.uses >= *times) {
+            mismatches.push(Mismatch::new(
+                "times",
+                format!("at most {times} uses"),
+                format!("{} uses", mock.uses),
+            ));
+        }
+        if let Some(scenario) = &rule.when.scenario {
+            let current = self.scenario_state(&scenario.name);
+            if current != scenario.state {
+                mismatches.push(Mismatch::new(
+                    format!("scenario {}", scenario.name),
+                    &scenario.state,
+                    current,
+                ));
+            }
+        }
+        mismatches
+    }
+
+    fn scenario_state(&self, name: &str) -> &str {
+        self.scenarios
+            .get(name)
+            .map(String::as_str)
+            .unwrap_or(SCENARIO_STARTED)
+    }
+
+    /// Every scenario referenced by a mock or moved by one, by name.
+    fn scenario_states(&self) -> Vec<Scenario> {
+        let names = self
+            .mocks
+            .iter()
+            .filter_map(|mock| mock.rule.when.scenario.as_ref())
+            .map(|scenario| scenario.name.as_str())
+            .chain(self.scenarios.keys().map(String::as_str))
+            .collect::<BTreeSet<_>>();
+        names
+            .into_iter()
+            .map(|name| Scenario {
+                name: name.to_string(),
+                state: self.scenario_state(name).to_string(),
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+struct InstalledMock {
+    id: MockId,
+    rule: MockRule,
+    patterns: Patterns,
+    /// How many requests the mock has answered.
+    uses: u32,
+    /// The request the mock was recorded from, if it was.
+    recorded: Option<record::RecordingKey>,
+}
+
+/// A rule's path and JSON predicate patterns, compiled once when the mock is
+/// installed rather than on every request it is checked against.
+#[derive(Debug)]
+struct Patterns {
+    path: path::CompiledPath,
+    /// One per JSON predicate, set for `Matches` conditions.
+    json_predicates: Vec<Option<regex::Regex>>,
+}
+
+/// The response a mock gives the `uses`th time it answers: its own response
+/// first, then each of its further responses in turn.
+fn canned_response(then: &ThenState, uses: u32) -> CannedResponse {
+    let further = then.responses.len();
+    let index = match then.sequence {
+        SequencePolicy::RepeatLast => (uses as usize).min(further),
+        SequencePolicy::Cycle => uses as usize % (further + 1),
+    };
+    match index.checked_sub(1) {
+        Some(index) => then.responses[index].clone(),
+        None => CannedResponse {
+            status: then.status,
+            headers: then.headers.clone(),
+            body: then.body.clone(),
+        },
+    }
+}
+
+impl InstanceState {
+    fn new() -> Self {
+        Self {
+            mocks: vec![],
+            requests: vec![],
+            cors: CorsPolicy::default(),
+            scenarios: HashMap::new(),
+            listener: None,
+            last_used: Instant::now(),
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct SequentialState {
     mock_port: u16,
-    instance: Instance,
+    instances: Instances,
+    mode: Mode,
+    isolated: bool,
+    instance_listeners: bool,
+    idle_ttl: Option<Duration>,
+    upstream: Option<Passthrough>,
+    interception: Option<Arc<Interception>>,
+    tls: Option<TlsIdentity>,
 }
 
 impl SequentialState {
     pub fn new(mock_port: u16) -> Self {
         Self {
             mock_port,
-            instance: Arc::default(),
+            instances: Arc::default(),
+            mode: Mode::default(),
+            isolated: false,
+            instance_listeners: false,
+            idle_ttl: None,
+            upstream: None,
+            interception: None,
+            tls: None,
+        }
+    }
+
+    pub fn with_mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Keeps every created instance alive side by side rather than replacing
+    /// the previous one, so tests sharing a server can run in parallel. Mock
+    /// requests pick their instance by `/instance/{id}` prefix or
+    /// [`INSTANCE_HEADER`].
+    pub fn isolated(mut self) -> Self {
+        self.isolated = true;
+        self
+    }
+
+    /// Binds a fresh mock listener for every created instance, serving only
+    /// that instance's rules, and hands out its port as the instance URL.
+    pub fn with_instance_listeners(mut self) -> Self {
+        self.instance_listeners = true;
+        self
+    }
+
+    /// Evicts instances that have seen neither a command nor a mock request
+    /// for `ttl`.
+    pub fn with_idle_ttl(mut self, ttl: Duration) -> Self {
+        self.idle_ttl = Some(ttl);
+        self
+    }
+
+    /// Reverse proxies to `upstream`, e.g. `http://localhost:8080`: in
+    /// [`Mode::Proxy`] and [`Mode::Record`] unmatched requests go there with
+    /// their path and query kept, rather than to the host they name.
+    pub fn with_upstream(
+        mut self,
+        upstream: &str,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let upstream = Passthrough::to(upstream);
+        passthrough::validate(&upstream)?;
+        self.upstream = Some(upstream);
+        Ok(self)
+    }
+
+    /// Decrypts HTTPS sent through the proxy by `CONNECT`, serving it from
+    /// mocks like plain HTTP. Clients must trust the interception's
+    /// [`CertificateAuthority`].
+    pub fn with_interception(mut self, interception: Interception) -> Self {
+        self.interception = Some(Arc::new(interception));
+        self
+    }
+
+    /// Serves the mock and control ports, and any instance listeners, over
+    /// HTTPS with `identity`; instance URLs use `https://`.
+    pub fn with_tls(mut self, identity: TlsIdentity) -> Self {
+        self.tls = Some(identity);
+        self
+    }
+
+    /// The base URL of a listener on `port`.
+    fn origin(&self, port: u16) -> String {
+        let scheme = match self.tls {
+            Some(_) => "https",
+            None => "http",
+        };
+        format!("{scheme}://localhost:{port}")
+    }
+
+    /// The instance a mock request is for: the one it was routed to, or the
+    /// only one when instances aren't isolated.
+    fn find_instance<'a>(
+        &self,
+        instances: &'a mut HashMap<InstanceId, InstanceState>,
+        instance_id: Option<&InstanceId>,
+    ) -> Option<&'a mut InstanceState> {
+        match instance_id {
+            Some(id) => instances.get_mut(id),
+            None if self.isolated => None,
+            None => instances.values_mut().next(),
+        }
+    }
+
+    async fn touch(&self, instance_id: &InstanceId) {
+        if let Some(instance) = self.instances.write().await.get_mut(instance_id) {
+            instance.last_used = Instant::now();
         }
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
 pub enum Mode {
+    #[default]
     Mock,
     Proxy,
+    /// Proxies unmatched requests like [`Mode::Proxy`], turning each answer
+    /// into a mock so the exchange can be replayed.
+    Record,
+    /// Serves only installed mocks and fails unmatched requests with a 500.
+    Playback,
 }
 
 trait RequestMatch {
-    fn matches(&self, req: &UnpackedRequest) -> bool;
-    fn priority(&self) -> u8;
+    fn mismatches(&self, patterns: &Patterns, req: &UnpackedRequest) -> Vec<Mismatch>;
+    fn priority(&self) -> Priority;
     fn method_match(method: &Method, req_method: &hyper::Method) -> bool;
 }
 
 impl RequestMatch for MockRule {
-    fn matches(&self, req: &UnpackedRequest) -> bool {
-        trace!(?req, "Checking if request matches");
-        let params_match = self.check_params_match(req);
-        let method_match = self
-            .when
-            .method
-            .as_ref()
-            .map(|m| Self::method_match(m, &req.method))
-            .unwrap_or(true);
-        let path_match = self.when.match_path == req.uri.path();
-        path_match && params_match && method_match
+    fn mismatches(&self, patterns: &Patterns, req: &UnpackedRequest) -> Vec<Mismatch> {
+        let mut mismatches = vec![];
+        if !path::path_matches(&patterns.path, req.uri.path()) {
+            mismatches.push(Mismatch::new(
+                "path",
+                path::describe(&self.when.match_path),
+                req.uri.path(),
+            ));
+        }
+        if let Some(method) = &self.when.method {
+            if !Self::method_match(method, &req.method) {
+                mismatches.push(Mismatch::new(
+                    "method",
+                    method.as_str(),
+                    req.method.as_str(),
+                ));
+            }
+        }
+        mismatches.extend(self.header_mismatches(req));
+        mismatches.extend(self.query_mismatches(req));
+        if self.expects_json() {
+            mismatches.extend(self.json_body_mismatches(patterns, req));
+        } else {
+            mismatches.extend(self.params_mismatch(req));
+        }
+        mismatches
     }
 
-    fn priority(&self) -> u8 {
+    fn priority(&self) -> Priority {
         let form_data = if self.when.form_data.is_empty() { 0 } else { 1 };
         let method = if self.when.method.is_some() { 1 } else { 0 };
-        form_data + method
+        let headers = self.when.headers.len() as u32;
+        let query = self.when.query.len() as u32 + u32::from(self.when.exact_query);
+        let json_body = if self.when.json_body.is_some() { 1 } else { 0 };
+        let json_predicates = self.when.json_predicates.len() as u32;
+        let path = match &self.when.match_path {
+            PathMatcher::Exact(_) => PathSpecificity::Exact,
+            PathMatcher::Template(template) => {
+                PathSpecificity::Template(path::literal_segments(template))
+            }
+            PathMatcher::Regex(_) | PathMatcher::Glob(_) => PathSpecificity::Pattern,
+        };
+        Priority {
+            path,
+            matchers: form_data + method + headers + query + json_body + json_predicates,
+        }
     }
 
     fn method_match(method: &Method, req_method: &hyper::Method) -> bool {
-        matches!(
-            (method, req_method),
-            (Method::GET, &hyper::Method::GET)
-                | (Method::POST, &hyper::Method::POST)
-                | (Method::PUT, &hyper::Method::PUT)
-                | (Method::DELETE, &hyper::Method::DELETE)
-        )
+        method.as_str() == req_method.as_str()
     }
 }
 
+/// Rules are tried in descending priority: the most specific path wins first,
+/// then the rule with the most matchers.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Priority {
+    path: PathSpecificity,
+    matchers: u32,
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum PathSpecificity {
+    Pattern,
+    /// Templates are ranked by their number of literal segments.
+    Template(u32),
+    Exact,
+}
+
+const MISSING: &str = "<missing>";
+
 impl MockRule {
-    fn check_params_match(&self, req: &UnpackedRequest) -> bool {
+    fn params_mismatch(&self, req: &UnpackedRequest) -> Option<Mismatch> {
         let params = form_urlencoded::parse(req.body.as_ref())
             .into_owned()
             .collect::<HashMap<String, String>>();
@@ -380,7 +1122,142 @@ impl MockRule {
             .form_data
             .iter()
             .all(|(key, value)| params.get(key).map(|v| v == value).unwrap_or(false));
-        correct_param_count && correct_params
+        if correct_param_count && correct_params {
+            return None;
+        }
+        let expected = form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(&self.when.form_data)
+            .finish();
+        Some(Mismatch::new(
+            "form_data",
+            expected,
+            String::from_utf8_lossy(&req.body),
+        ))
+    }
+
+    fn header_mismatches<'a>(
+        &'a self,
+        req: &'a UnpackedRequest,
+    ) -> impl Iterator<Item = Mismatch> + 'a {
+        self.when.headers.iter().filter_map(|(name, value)| {
+            let actual = req.headers.get_all(name.as_str());
+            if actual.iter().any(|v| v.as_bytes() == value.as_bytes()) {
+                return None;
+            }
+            let actual = actual
+                .iter()
+                .map(|v| String::from_utf8_lossy(v.as_bytes()).into_owned())
+                .collect::<Vec<_>>();
+            Some(Mismatch::new(
+                format!("header {name}"),
+                value,
+                describe_values(&actual),
+            ))
+        })
+    }
+
+    fn query_mismatches(&self, req: &UnpackedRequest) -> Vec<Mismatch> {
+        let params = form_urlencoded::parse(req.uri.query().unwrap_or_default().as_bytes())
+            .into_owned()
+            .collect::<Vec<(String, String)>>();
+        self.when
+            .query
+            .iter()
+            .filter_map(|(name, expected)| {
+                let actual = params
+                    .iter()
+                    .filter(|(key, _)| key == name)
+                    .map(|(_, value)| value.clone())
+                    .collect::<Vec<_>>();
+                let matched = actual.iter().any(|value| match expected {
+                    QueryValue::Exact(expected) => value == expected,
+                    QueryValue::Present => value.is_empty(),
+                    QueryValue::Any => true,
+                });
+                let expected = match expected {
+                    QueryValue::Exact(expected) => expected.as_str(),
+                    QueryValue::Present => "<present>",
+                    QueryValue::Any => "<any>",
+                };
+                (!matched).then(|| {
+                    Mismatch::new(format!("query {name}"), expected, describe_values(&actual))
+                })
+            })
+            .chain(
+                params
+                    .iter()
+                    .filter(|(key, _)| {
+                        self.when.exact_query
+                            && !self.when.query.iter().any(|(name, _)| name == key)
+                    })
+                    .map(|(key, value)| Mismatch::new(format!("query {key}"), "<absent>", value)),
+            )
+            .collect()
+    }
+
+    fn expects_json(&self) -> bool {
+        self.when.json_body.is_some() || !self.when.json_predicates.is_empty()
+    }
+
+    fn json_body_mismatches(&self, patterns: &Patterns, req: &UnpackedRequest) -> Vec<Mismatch> {
+        let Ok(body) = serde_json::from_slice::<serde_json::Value>(&req.body) else {
+            return vec![Mismatch::new(
+                "json_body",
+                "<json>",
+                String::from_utf8_lossy(&req.body),
+            )];
+        };
+        let body_mismatch = match &self.when.json_body {
+            Some(JsonBody::Exact(expected)) if &body != expected => Some(expected),
+            Some(JsonBody::Partial(expected)) if !json::json_contains(&body, expected) => {
+                Some(expected)
+            }
+            _ => None,
+        }
+        .map(|expected| Mismatch::new("json_body", expected.to_string(), body.to_string()));
+        body_mismatch
+            .into_iter()
+            .chain(
+                self.when
+                    .json_predicates
+                    .iter()
+                    .zip(&patterns.json_predicates)
+                    .filter_map(|(predicate, regex)| {
+                        json::predicate_mismatch(predicate, regex.as_ref(), &body)
+                    }),
+            )
+            .collect()
+    }
+
+    /// Checks the rule is well formed and compiles its patterns.
+    fn compile(&self) -> Result<Patterns, String> {
+        let path = path::compile(&self.when.match_path)?;
+        latency::validate(self.then.delay.as_ref(), self.then.dribble.as_ref())?;
+        if let Some(passthrough) = &self.then.passthrough {
+            passthrough::validate(passthrough)?;
+        }
+        if self.then.transition_to.is_some() && self.when.scenario.is_none() {
+            return Err("A scenario transition needs the rule to be in a scenario".to_string());
+        }
+        let json_predicates = self
+            .when
+            .json_predicates
+            .iter()
+            .map(json::compile_predicate)
+            .collect::<Result<_, _>>()
+            .map_err(|err| err.to_string())?;
+        Ok(Patterns {
+            path,
+            json_predicates,
+        })
+    }
+}
+
+fn describe_values(values: &[String]) -> String {
+    if values.is_empty() {
+        MISSING.to_string()
+    } else {
+        values.join(", ")
     }
 }
 
@@ -390,6 +1267,7 @@ struct UnpackedRequest {
     headers: hyper::HeaderMap,
     uri: hyper::Uri,
     body: Bytes,
+    path_params: Vec<(String, String)>,
 }
 
 impl UnpackedRequest {
@@ -412,6 +1290,27 @@ impl UnpackedRequest {
             headers,
             uri,
             body,
+            path_params: vec![],
+        }
+    }
+
+    fn to_recorded(&self, matched: Option<MockId>) -> RecordedRequest {
+        RecordedRequest {
+            method: self.method.to_string(),
+            path: self.uri.path().to_string(),
+            query: self.uri.query().map(String::from),
+            headers: self
+                .headers
+                .iter()
+                .map(|(name, value)| {
+                    let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+                    (name.to_string(), value)
+                })
+                .collect(),
+            body: self.body.to_vec(),
+            path_params: self.path_params.clone(),
+            matched,
+            near_misses: vec![],
         }
     }
 }
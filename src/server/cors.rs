@@ -0,0 +1,98 @@
+use http_body_util::Full;
+use hyper::{
+    body::Bytes,
+    header::{
+        HeaderValue, ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS,
+        ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_EXPOSE_HEADERS, ORIGIN, VARY,
+    },
+    HeaderMap, Response,
+};
+
+use crate::interchange::CorsPolicy;
+
+use super::UnpackedRequest;
+
+/// Like the `CorsLayer` this replaces, any `OPTIONS` request is treated as a
+/// preflight.
+pub(super) fn is_preflight(req: &UnpackedRequest) -> bool {
+    req.method == hyper::Method::OPTIONS
+}
+
+/// The answer to a preflight no mock took over, or `None` when CORS is
+/// disabled and the request should be handled like any other.
+pub(super) fn preflight_response(
+    policy: &CorsPolicy,
+    req: &UnpackedRequest,
+) -> Option<Response<Full<Bytes>>> {
+    let mut headers = HeaderMap::new();
+    match policy {
+        CorsPolicy::Disabled => return None,
+        CorsPolicy::Permissive => {
+            headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, HeaderValue::from_static("*"));
+            headers.insert(ACCESS_CONTROL_ALLOW_METHODS, HeaderValue::from_static("*"));
+            headers.insert(ACCESS_CONTROL_ALLOW_HEADERS, HeaderValue::from_static("*"));
+        }
+        CorsPolicy::Restricted {
+            allowed_methods,
+            allowed_headers,
+            ..
+        } => {
+            headers.insert(VARY, HeaderValue::from_static("origin"));
+            if let Some(origin) = allowed_origin(policy, req) {
+                headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+                insert_list(&mut headers, ACCESS_CONTROL_ALLOW_METHODS, allowed_methods);
+                insert_list(&mut headers, ACCESS_CONTROL_ALLOW_HEADERS, allowed_headers);
+            }
+        }
+    }
+
+    let mut response = Response::builder()
+        .status(200)
+        .body(Full::new(Bytes::new()))
+        .unwrap();
+    *response.headers_mut() = headers;
+    Some(response)
+}
+
+/// Adds CORS headers to an actual (non-preflight) response. Headers already
+/// set by a mock or an upstream are left alone.
+pub(super) fn apply<B>(policy: &CorsPolicy, req: &UnpackedRequest, response: &mut Response<B>) {
+    let headers = response.headers_mut();
+    match policy {
+        CorsPolicy::Disabled => {}
+        CorsPolicy::Permissive => {
+            headers
+                .entry(ACCESS_CONTROL_ALLOW_ORIGIN)
+                .or_insert(HeaderValue::from_static("*"));
+            headers
+                .entry(ACCESS_CONTROL_EXPOSE_HEADERS)
+                .or_insert(HeaderValue::from_static("*"));
+        }
+        CorsPolicy::Restricted { .. } => {
+            headers.append(VARY, HeaderValue::from_static("origin"));
+            if let Some(origin) = allowed_origin(policy, req) {
+                headers.entry(ACCESS_CONTROL_ALLOW_ORIGIN).or_insert(origin);
+            }
+        }
+    }
+}
+
+fn allowed_origin(policy: &CorsPolicy, req: &UnpackedRequest) -> Option<HeaderValue> {
+    let CorsPolicy::Restricted {
+        allowed_origins, ..
+    } = policy
+    else {
+        return None;
+    };
+    let origin = req.headers.get(ORIGIN)?;
+    allowed_origins
+        .iter()
+        .any(|allowed| allowed == "*" || allowed.as_bytes() == origin.as_bytes())
+        .then(|| origin.clone())
+}
+
+fn insert_list(headers: &mut HeaderMap, name: hyper::header::HeaderName, values: &[String]) {
+    if let Ok(value) = HeaderValue::from_str(&values.join(", ")) {
+        headers.insert(name, value);
+    }
+}
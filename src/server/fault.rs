@@ -0,0 +1,112 @@
+use std::{
+    convert::Infallible,
+    io,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use hyper::body::{Body, Bytes, Frame};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::TcpStream,
+};
+
+use crate::interchange::Fault;
+
+/// Sent instead of a response for [`Fault::Garbage`].
+const GARBAGE: &[u8] = b"\x00\xff\xfeNOT HTTP\r\n\r\n\x7f";
+
+/// Lets the handler tell the connection it is serving to misbehave.
+#[derive(Debug, Clone, Default)]
+pub(super) struct FaultTrigger(Arc<Mutex<Option<Fault>>>);
+
+impl FaultTrigger {
+    pub(super) fn trigger(&self, fault: Fault) {
+        *self.0.lock().unwrap() = Some(fault);
+    }
+
+    fn triggered(&self) -> Option<Fault> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+/// A mock connection that, once triggered, discards whatever hyper writes
+/// and breaks the connection instead.
+pub(super) struct FaultyStream {
+    stream: TcpStream,
+    trigger: FaultTrigger,
+    garbage: &'static [u8],
+}
+
+impl FaultyStream {
+    pub(super) fn new(stream: TcpStream, trigger: FaultTrigger) -> Self {
+        Self {
+            stream,
+            trigger,
+            garbage: GARBAGE,
+        }
+    }
+}
+
+impl AsyncRead for FaultyStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for FaultyStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        match this.trigger.triggered() {
+            None | Some(Fault::HeadersThenStall) => Pin::new(&mut this.stream).poll_write(cx, buf),
+            Some(Fault::ConnectionReset) => {
+                // A zero linger makes the close send RST rather than FIN.
+                this.stream.set_linger(Some(Duration::ZERO))?;
+                Poll::Ready(Err(io::ErrorKind::ConnectionReset.into()))
+            }
+            Some(Fault::EmptyReply) => Poll::Ready(Err(io::ErrorKind::ConnectionAborted.into())),
+            Some(Fault::Garbage) => {
+                while !this.garbage.is_empty() {
+                    let written =
+                        std::task::ready!(Pin::new(&mut this.stream).poll_write(cx, this.garbage))?;
+                    this.garbage = &this.garbage[written..];
+                }
+                Poll::Ready(Err(io::ErrorKind::ConnectionAborted.into()))
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_shutdown(cx)
+    }
+}
+
+/// A body that never produces a frame, so the client gets the headers and
+/// then waits.
+pub(super) struct StalledBody;
+
+impl Body for StalledBody {
+    type Data = Bytes;
+    type Error = Infallible;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Infallible>>> {
+        Poll::Pending
+    }
+}
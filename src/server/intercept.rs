@@ -0,0 +1,216 @@
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    sync::{Arc, Mutex},
+};
+
+use hyper::{body::Incoming, server::conn::http1, service::service_fn, upgrade, Request, Response};
+use hyper_util::rt::TokioIo;
+use rcgen::{
+    BasicConstraints, Certificate, CertificateParams, DnType, IsCa, KeyPair, KeyUsagePurpose,
+};
+use rustls::{
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer},
+    ClientConfig, RootCertStore, ServerConfig,
+};
+use tokio_rustls::TlsAcceptor;
+use tracing::{info, trace, warn};
+
+use crate::{
+    hyper_helpers::{crypto_provider, tls_client_config},
+    interchange::{InstanceId, Passthrough},
+};
+
+use super::{fault::FaultTrigger, mock_handler, tunnel, MockBody, SequentialState};
+
+/// Signs the certificates presented for intercepted hosts. Clients going
+/// through the proxy must trust [`CertificateAuthority::cert_pem`].
+pub struct CertificateAuthority {
+    cert: Certificate,
+    key: KeyPair,
+    cert_pem: String,
+}
+
+impl CertificateAuthority {
+    /// A fresh authority; save its PEMs to reuse it, so clients only need to
+    /// trust it once.
+    pub fn generate() -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let mut params = CertificateParams::default();
+        params
+            .distinguished_name
+            .push(DnType::CommonName, "Pulcinella CA");
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        params.key_usages = vec![
+            KeyUsagePurpose::KeyCertSign,
+            KeyUsagePurpose::CrlSign,
+            KeyUsagePurpose::DigitalSignature,
+        ];
+        let key = KeyPair::generate()?;
+        let cert = params.self_signed(&key)?;
+        Ok(Self {
+            cert_pem: cert.pem(),
+            cert,
+            key,
+        })
+    }
+
+    /// Loads an authority from its PEM encoded certificate and private key.
+    pub fn from_pem(
+        cert_pem: &str,
+        key_pem: &str,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let key = KeyPair::from_pem(key_pem)?;
+        // Minted certificates only refer to the authority's subject and key,
+        // so signing with a re-issued copy chains to the original.
+        let cert = CertificateParams::from_ca_cert_pem(cert_pem)?.self_signed(&key)?;
+        Ok(Self {
+            cert,
+            key,
+            cert_pem: cert_pem.to_string(),
+        })
+    }
+
+    pub fn cert_pem(&self) -> &str {
+        &self.cert_pem
+    }
+
+    pub fn key_pem(&self) -> String {
+        self.key.serialize_pem()
+    }
+
+    fn mint(&self, host: &str) -> Result<ServerConfig, Box<dyn std::error::Error + Send + Sync>> {
+        let mut params = CertificateParams::new(vec![host.to_string()])?;
+        params.distinguished_name.push(DnType::CommonName, host);
+        let key = KeyPair::generate()?;
+        let cert = params.signed_by(&key, &self.cert, &self.key)?;
+        let mut config = ServerConfig::builder_with_provider(crypto_provider())
+            .with_safe_default_protocol_versions()?
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![cert.der().clone()],
+                PrivateKeyDer::Pkcs8(key.serialize_der().into()),
+            )?;
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+        Ok(config)
+    }
+}
+
+impl std::fmt::Debug for CertificateAuthority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CertificateAuthority")
+            .finish_non_exhaustive()
+    }
+}
+
+/// Terminates TLS for `CONNECT` tunnels with a certificate minted for the
+/// target host, so HTTPS requests go through mock matching and proxy
+/// fallback like plain HTTP ones.
+#[derive(Debug)]
+pub struct Interception {
+    authority: CertificateAuthority,
+    upstream_roots: RootCertStore,
+    upstream: Arc<ClientConfig>,
+    minted: Mutex<HashMap<String, Arc<ServerConfig>>>,
+}
+
+impl Interception {
+    /// Upstreams are trusted if their certificates chain to a public root.
+    pub fn new(authority: CertificateAuthority) -> Self {
+        let upstream_roots = RootCertStore::empty();
+        Self {
+            authority,
+            upstream: tls_client_config(&upstream_roots),
+            upstream_roots,
+            minted: Mutex::default(),
+        }
+    }
+
+    /// Also trusts upstreams whose certificates chain to `cert_pem`, such as
+    /// a self-signed test server.
+    pub fn trust_upstream(
+        mut self,
+        cert_pem: &str,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let cert = CertificateDer::from_pem_slice(cert_pem.as_bytes())?;
+        self.upstream_roots.add(cert)?;
+        self.upstream = tls_client_config(&self.upstream_roots);
+        Ok(self)
+    }
+
+    pub fn authority(&self) -> &CertificateAuthority {
+        &self.authority
+    }
+
+    pub(super) fn upstream(&self) -> &Arc<ClientConfig> {
+        &self.upstream
+    }
+
+    fn server_config(
+        &self,
+        host: &str,
+    ) -> Result<Arc<ServerConfig>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut minted = self.minted.lock().unwrap();
+        if let Some(config) = minted.get(host) {
+            return Ok(config.clone());
+        }
+        let config = Arc::new(self.authority.mint(host)?);
+        minted.insert(host.to_string(), config.clone());
+        Ok(config)
+    }
+}
+
+/// Answers a `CONNECT host:port`, then serves the decrypted requests as mock
+/// requests whose proxy fallback is `https://host:port`. `faults` belongs to
+/// the client's connection, which carries the tunnel, so faults break the
+/// real socket underneath the TLS session.
+pub(super) async fn connect(
+    mut req: Request<Incoming>,
+    state: SequentialState,
+    instance: Option<InstanceId>,
+    interception: Arc<Interception>,
+    faults: FaultTrigger,
+) -> Result<Response<MockBody>, Infallible> {
+    let Some(authority) = req.uri().authority().cloned() else {
+        return Ok(tunnel::status(400));
+    };
+    let config = match interception.server_config(authority.host()) {
+        Ok(config) => config,
+        Err(err) => {
+            warn!(%authority, ?err, "Cannot mint certificate");
+            return Ok(tunnel::status(500));
+        }
+    };
+    info!(%authority, "Intercepting connection");
+    let mut state = state;
+    state.upstream = Some(Passthrough::to(&format!("https://{authority}")));
+
+    tokio::task::spawn(async move {
+        let upgraded = match upgrade::on(&mut req).await {
+            Ok(upgraded) => upgraded,
+            Err(err) => {
+                warn!(%authority, ?err, "Cannot upgrade intercepted connection");
+                return;
+            }
+        };
+        let stream = match TlsAcceptor::from(config)
+            .accept(TokioIo::new(upgraded))
+            .await
+        {
+            Ok(stream) => stream,
+            Err(err) => {
+                warn!(%authority, ?err, "TLS handshake with client failed");
+                return;
+            }
+        };
+        let service = service_fn(move |req| {
+            mock_handler(req, state.clone(), instance.clone(), faults.clone())
+        });
+        if let Err(err) = http1::Builder::new()
+            .serve_connection(TokioIo::new(stream), service)
+            .await
+        {
+            trace!(%authority, ?err, "Intercepted connection closed");
+        }
+    });
+    Ok(tunnel::status(200))
+}
@@ -0,0 +1,171 @@
+use regex::Regex;
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::interchange::{JsonCondition, JsonPredicate, JsonSelector, Mismatch};
+
+/// Whether `actual` is a superset of `expected`: objects may carry extra keys
+/// and arrays extra elements, everything else must be equal.
+pub(super) fn json_contains(actual: &Value, expected: &Value) -> bool {
+    match (actual, expected) {
+        (Value::Object(actual), Value::Object(expected)) => expected.iter().all(|(key, value)| {
+            actual
+                .get(key)
+                .map(|actual| json_contains(actual, value))
+                .unwrap_or(false)
+        }),
+        (Value::Array(actual), Value::Array(expected)) => expected
+            .iter()
+            .all(|value| actual.iter().any(|actual| json_contains(actual, value))),
+        _ => actual == expected,
+    }
+}
+
+/// `regex` is the predicate's `Matches` pattern, as returned by
+/// [`compile_predicate`].
+pub(super) fn predicate_matches(
+    predicate: &JsonPredicate,
+    regex: Option<&Regex>,
+    body: &Value,
+) -> bool {
+    let Ok(selected) = select(&predicate.selector, body) else {
+        return false;
+    };
+    match (&predicate.condition, selected) {
+        (JsonCondition::Exists, selected) => selected.is_some(),
+        (JsonCondition::Equals(expected), Some(value)) => value == expected,
+        (JsonCondition::Matches(_), Some(value)) => regex.is_some_and(|regex| match value {
+            Value::String(value) => regex.is_match(value),
+            value => regex.is_match(&value.to_string()),
+        }),
+        (_, None) => false,
+    }
+}
+
+pub(super) fn predicate_mismatch(
+    predicate: &JsonPredicate,
+    regex: Option<&Regex>,
+    body: &Value,
+) -> Option<Mismatch> {
+    if predicate_matches(predicate, regex, body) {
+        return None;
+    }
+    let field = match &predicate.selector {
+        JsonSelector::Pointer(pointer) => format!("json {pointer}"),
+        JsonSelector::Path(path) => format!("json {path}"),
+    };
+    let expected = match &predicate.condition {
+        JsonCondition::Equals(expected) => expected.to_string(),
+        JsonCondition::Exists => "<present>".to_string(),
+        JsonCondition::Matches(pattern) => format!("/{pattern}/"),
+    };
+    let actual = select(&predicate.selector, body)
+        .ok()
+        .flatten()
+        .map(Value::to_string)
+        .unwrap_or_else(|| super::MISSING.to_string());
+    Some(Mismatch::new(field, expected, actual))
+}
+
+/// Checks a predicate can be evaluated, so bad expressions are rejected when
+/// the mock is installed rather than silently never matching, and compiles
+/// its `Matches` pattern.
+pub(super) fn compile_predicate(predicate: &JsonPredicate) -> Result<Option<Regex>, JsonPathError> {
+    match &predicate.selector {
+        JsonSelector::Pointer(pointer) => validate_pointer(pointer)?,
+        JsonSelector::Path(path) => {
+            parse_path(path)?;
+        }
+    }
+    match &predicate.condition {
+        JsonCondition::Matches(pattern) => Regex::new(pattern)
+            .map(Some)
+            .map_err(|_| JsonPathError::Regex(pattern.clone())),
+        _ => Ok(None),
+    }
+}
+
+pub(super) fn select<'a>(
+    selector: &JsonSelector,
+    body: &'a Value,
+) -> Result<Option<&'a Value>, JsonPathError> {
+    match selector {
+        JsonSelector::Pointer(pointer) => Ok(body.pointer(pointer)),
+        JsonSelector::Path(path) => {
+            Ok(parse_path(path)?
+                .iter()
+                .try_fold(body, |value, step| match step {
+                    Step::Key(key) => value.get(key),
+                    Step::Index(index) => value.get(index),
+                }))
+        }
+    }
+}
+
+/// An RFC 6901 pointer is empty or a series of `/`-prefixed tokens, where `~`
+/// only appears escaped as `~0` or `~1`.
+fn validate_pointer(pointer: &str) -> Result<(), JsonPathError> {
+    let invalid = || JsonPathError::Pointer(pointer.to_string());
+    if !pointer.is_empty() && !pointer.starts_with('/') {
+        return Err(invalid());
+    }
+    let mut chars = pointer.chars();
+    while let Some(c) = chars.next() {
+        if c == '~' && !matches!(chars.next(), Some('0' | '1')) {
+            return Err(invalid());
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+enum Step {
+    Key(String),
+    Index(usize),
+}
+
+/// Parses the single-value subset of JSONPath: `$` followed by `.key`,
+/// `['key']` and `[index]` steps.
+fn parse_path(path: &str) -> Result<Vec<Step>, JsonPathError> {
+    let invalid = || JsonPathError::Path(path.to_string());
+    let mut rest = path.strip_prefix('$').ok_or_else(invalid)?;
+    let mut steps = vec![];
+
+    while !rest.is_empty() {
+        if let Some(after_dot) = rest.strip_prefix('.') {
+            let end = after_dot.find(['.', '[']).unwrap_or(after_dot.len());
+            if end == 0 {
+                return Err(invalid());
+            }
+            steps.push(Step::Key(after_dot[..end].to_string()));
+            rest = &after_dot[end..];
+        } else if let Some(after_bracket) = rest.strip_prefix('[') {
+            let end = after_bracket.find(']').ok_or_else(invalid)?;
+            let inner = &after_bracket[..end];
+            let quoted = inner
+                .strip_prefix('\'')
+                .and_then(|i| i.strip_suffix('\''))
+                .or_else(|| inner.strip_prefix('"').and_then(|i| i.strip_suffix('"')));
+            let step = match quoted {
+                Some(key) => Step::Key(key.to_string()),
+                None => Step::Index(inner.parse().map_err(|_| invalid())?),
+            };
+            steps.push(step);
+            rest = &after_bracket[end + 1..];
+        } else {
+            return Err(invalid());
+        }
+    }
+
+    Ok(steps)
+}
+
+#[derive(Debug, Error)]
+pub(super) enum JsonPathError {
+    #[error("Invalid JSONPath expression: {0}")]
+    Path(String),
+    #[error("Invalid JSON pointer: {0}")]
+    Pointer(String),
+    #[error("Invalid regular expression: {0}")]
+    Regex(String),
+}
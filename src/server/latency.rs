@@ -0,0 +1,113 @@
+use std::{
+    convert::Infallible,
+    future::Future,
+    pin::Pin,
+    task::{ready, Context, Poll},
+    time::Duration,
+};
+
+use hyper::body::{Body, Bytes, Frame};
+use rand::Rng;
+use rand_distr::{Distribution, LogNormal};
+use tokio::time::Sleep;
+
+use crate::interchange::{Delay, Dribble};
+
+/// How long to wait before this response; random delays are sampled afresh
+/// every time.
+pub(super) fn sample(delay: &Delay) -> Duration {
+    match delay {
+        Delay::Fixed(delay) => *delay,
+        Delay::Uniform { min, max } => Duration::from_secs_f64(
+            rand::thread_rng().gen_range(min.as_secs_f64()..=max.as_secs_f64()),
+        ),
+        Delay::LogNormal {
+            median,
+            sigma,
+            min,
+            max,
+        } => LogNormal::new(median.as_secs_f64().ln(), *sigma)
+            .map(|distribution| {
+                // Clamp before converting: the tail can overflow a `Duration`.
+                let seconds = distribution
+                    .sample(&mut rand::thread_rng())
+                    .clamp(min.as_secs_f64(), max.as_secs_f64());
+                Duration::from_secs_f64(seconds)
+            })
+            .unwrap_or(*median),
+    }
+}
+
+/// Beyond this the log-normal tail is effectively unbounded.
+const MAX_SIGMA: f64 = 10.0;
+
+pub(super) fn validate(delay: Option<&Delay>, dribble: Option<&Dribble>) -> Result<(), String> {
+    match delay {
+        Some(Delay::Uniform { min, max } | Delay::LogNormal { min, max, .. }) if min > max => {
+            return Err(format!(
+                "Delay minimum {min:?} is above its maximum {max:?}"
+            ));
+        }
+        Some(Delay::LogNormal { median, sigma, .. })
+            if median.is_zero() || !(0.0..=MAX_SIGMA).contains(sigma) =>
+        {
+            return Err(format!(
+                "Log-normal delay needs a positive median and a sigma between 0 and {MAX_SIGMA}"
+            ));
+        }
+        _ => {}
+    }
+    match dribble {
+        Some(dribble) if dribble.chunk_size == 0 => {
+            Err("Dribble chunk size must be positive".to_string())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// A body sent a chunk at a time with a pause between chunks. Its length is
+/// unknown up front, so hyper sends it chunked.
+pub(super) struct DribbleBody {
+    remaining: Bytes,
+    chunk_size: usize,
+    interval: Duration,
+    pause: Option<Pin<Box<Sleep>>>,
+}
+
+impl DribbleBody {
+    pub(super) fn new(body: Bytes, dribble: &Dribble) -> Self {
+        Self {
+            remaining: body,
+            chunk_size: dribble.chunk_size,
+            interval: dribble.interval,
+            pause: None,
+        }
+    }
+}
+
+impl Body for DribbleBody {
+    type Data = Bytes;
+    type Error = Infallible;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Infallible>>> {
+        let this = self.get_mut();
+        if this.remaining.is_empty() {
+            return Poll::Ready(None);
+        }
+        if let Some(pause) = this.pause.as_mut() {
+            ready!(pause.as_mut().poll(cx));
+        }
+        let chunk = this
+            .remaining
+            .split_to(this.chunk_size.min(this.remaining.len()));
+        this.pause = Some(Box::pin(tokio::time::sleep(this.interval)));
+        Poll::Ready(Some(Ok(Frame::data(chunk))))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.remaining.is_empty()
+    }
+}
@@ -0,0 +1,108 @@
+use std::{convert::Infallible, sync::Arc};
+
+use http_body_util::Full;
+use hyper::{
+    body::Bytes,
+    header::{HeaderName, HeaderValue, HOST},
+    HeaderMap, Response, Uri,
+};
+use rustls::ClientConfig;
+use tracing::info;
+
+use crate::interchange::Passthrough;
+
+use super::{proxy_response_to_response, send_upstream, ProxyError, UnpackedRequest};
+
+/// The upstream's answer to the request, with the rule's response headers
+/// applied.
+pub(super) async fn response(
+    req: &UnpackedRequest,
+    passthrough: &Passthrough,
+) -> Result<Response<Full<Bytes>>, Infallible> {
+    let mut response = match request_upstream(req, passthrough, None).await {
+        Ok(res) => proxy_response_to_response(res)
+            .await
+            .inspect(|_| info!(upstream = %passthrough.upstream, "Passed request through"))
+            .or_else(|e| e.to_response()),
+        Err(e) => e.to_response(),
+    }?;
+    set_headers(response.headers_mut(), &passthrough.response_headers);
+    Ok(response)
+}
+
+/// Sends the request to the passthrough's upstream; `https://` upstreams need
+/// `tls` to verify them.
+pub(super) async fn request_upstream(
+    req: &UnpackedRequest,
+    passthrough: &Passthrough,
+    tls: Option<&Arc<ClientConfig>>,
+) -> Result<Response<hyper::body::Incoming>, ProxyError> {
+    let upstream = passthrough
+        .upstream
+        .parse::<Uri>()
+        .map_err(|_| ProxyError::BadUpstream)?;
+    let authority = upstream.authority().ok_or(ProxyError::BadUpstream)?;
+    let (tls, default_port) = match upstream.scheme_str() {
+        Some("https") => (Some(tls.ok_or(ProxyError::BadUpstream)?), 443),
+        _ => (None, 80),
+    };
+    let address = format!(
+        "{}:{}",
+        authority.host(),
+        authority.port_u16().unwrap_or(default_port)
+    );
+    let uri = format!(
+        "{}{}",
+        upstream.path().trim_end_matches('/'),
+        req.uri
+            .path_and_query()
+            .map(|path_and_query| path_and_query.as_str())
+            .unwrap_or("/")
+    );
+
+    let mut headers = req.headers.clone();
+    if let Ok(host) = HeaderValue::from_str(authority.as_str()) {
+        headers.insert(HOST, host);
+    }
+    for name in &passthrough.remove_request_headers {
+        headers.remove(name.as_str());
+    }
+    set_headers(&mut headers, &passthrough.request_headers);
+    send_upstream(req, &address, &uri, headers, tls).await
+}
+
+fn set_headers(headers: &mut HeaderMap, values: &[(String, String)]) {
+    for (name, value) in values {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(value),
+        ) {
+            headers.insert(name, value);
+        }
+    }
+}
+
+pub(super) fn validate(passthrough: &Passthrough) -> Result<(), String> {
+    let upstream = passthrough
+        .upstream
+        .parse::<Uri>()
+        .map_err(|_| format!("Invalid passthrough upstream: {}", passthrough.upstream))?;
+    if upstream.scheme_str() != Some("http") || upstream.authority().is_none() {
+        return Err(format!(
+            "Passthrough upstream must be an http:// URL: {}",
+            passthrough.upstream
+        ));
+    }
+    let invalid_header = passthrough
+        .request_headers
+        .iter()
+        .chain(&passthrough.response_headers)
+        .find(|(name, value)| {
+            HeaderName::from_bytes(name.as_bytes()).is_err()
+                || HeaderValue::from_str(value).is_err()
+        });
+    match invalid_header {
+        Some((name, _)) => Err(format!("Invalid passthrough header: {name}")),
+        None => Ok(()),
+    }
+}
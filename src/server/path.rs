@@ -0,0 +1,111 @@
+use regex::Regex;
+
+use crate::interchange::PathMatcher;
+
+/// A path matcher with its pattern compiled, built once when the mock is
+/// installed.
+#[derive(Debug)]
+pub(super) enum CompiledPath {
+    Exact(String),
+    /// Regexes and globs, anchored to the whole path.
+    Pattern(Regex),
+    Template(String),
+}
+
+pub(super) fn path_matches(matcher: &CompiledPath, path: &str) -> bool {
+    match matcher {
+        CompiledPath::Exact(expected) => expected == path,
+        CompiledPath::Pattern(regex) => regex.is_match(path),
+        CompiledPath::Template(template) => template_captures(template, path).is_some(),
+    }
+}
+
+/// The segments a template captures from `path`, empty for other matchers.
+pub(super) fn captures(matcher: &PathMatcher, path: &str) -> Vec<(String, String)> {
+    match matcher {
+        PathMatcher::Template(template) => template_captures(template, path).unwrap_or_default(),
+        _ => vec![],
+    }
+}
+
+/// The number of literal segments in a template, used to prefer
+/// `/users/me` over `/users/{id}`.
+pub(super) fn literal_segments(template: &str) -> u32 {
+    template
+        .split('/')
+        .filter(|segment| template_variable(segment).is_none())
+        .count() as u32
+}
+
+fn template_captures(template: &str, path: &str) -> Option<Vec<(String, String)>> {
+    let template_segments = template.split('/').collect::<Vec<_>>();
+    let path_segments = path.split('/').collect::<Vec<_>>();
+    if template_segments.len() != path_segments.len() {
+        return None;
+    }
+
+    let mut captures = vec![];
+    for (expected, actual) in template_segments.into_iter().zip(path_segments) {
+        match template_variable(expected) {
+            Some(_) if actual.is_empty() => return None,
+            Some(name) => captures.push((name.to_string(), actual.to_string())),
+            None if expected != actual => return None,
+            None => {}
+        }
+    }
+    Some(captures)
+}
+
+fn template_variable(segment: &str) -> Option<&str> {
+    segment
+        .strip_prefix('{')
+        .and_then(|segment| segment.strip_suffix('}'))
+        .filter(|name| !name.is_empty())
+}
+
+/// How the matcher is shown in near-miss reports.
+pub(super) fn describe(matcher: &PathMatcher) -> String {
+    match matcher {
+        PathMatcher::Exact(path) => path.clone(),
+        PathMatcher::Regex(pattern) => format!("regex {pattern}"),
+        PathMatcher::Glob(glob) => format!("glob {glob}"),
+        PathMatcher::Template(template) => format!("template {template}"),
+    }
+}
+
+pub(super) fn compile(matcher: &PathMatcher) -> Result<CompiledPath, String> {
+    match matcher {
+        PathMatcher::Exact(path) => Ok(CompiledPath::Exact(path.clone())),
+        PathMatcher::Template(template) => Ok(CompiledPath::Template(template.clone())),
+        PathMatcher::Regex(pattern) => anchored(pattern)
+            .map(CompiledPath::Pattern)
+            .map_err(|_| format!("Invalid path regex: {pattern}")),
+        PathMatcher::Glob(glob) => anchored(&glob_to_regex(glob))
+            .map(CompiledPath::Pattern)
+            .map_err(|_| format!("Invalid path glob: {glob}")),
+    }
+}
+
+/// Path patterns always have to match the whole path.
+fn anchored(pattern: &str) -> Result<Regex, regex::Error> {
+    Regex::new(&format!("^(?:{pattern})$"))
+}
+
+/// `**` matches across segments, `*` within a single segment and `?` a single
+/// character of a segment; everything else is literal.
+fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::new();
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                regex.push_str(".*");
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex
+}
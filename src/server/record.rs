@@ -0,0 +1,145 @@
+use http_body_util::{BodyExt, Full};
+use hyper::{
+    body::Bytes,
+    header::{CONNECTION, CONTENT_LENGTH, TRANSFER_ENCODING},
+    Response,
+};
+use tracing::{info, warn};
+
+use crate::interchange::{
+    InstanceId, JsonBody, Method, MockId, MockRule, PathMatcher, QueryValue, SequencePolicy,
+    ThenState, WhenRules,
+};
+
+use super::{InstalledMock, RequestMatch, SequentialState, UnpackedRequest};
+
+/// What makes two recorded exchanges the same: later requests with the same
+/// method, path, query and body are answered by the first recording.
+#[derive(Debug, PartialEq, Eq)]
+pub(super) struct RecordingKey {
+    method: hyper::Method,
+    path: String,
+    query: String,
+    body: Bytes,
+}
+
+impl RecordingKey {
+    fn of(req: &UnpackedRequest) -> Self {
+        Self {
+            method: req.method.clone(),
+            path: req.uri.path().to_string(),
+            query: req.uri.query().unwrap_or_default().to_string(),
+            body: req.body.clone(),
+        }
+    }
+}
+
+/// Installs a mock replaying the upstream's response into the instance the
+/// request was for, unless the same request was recorded already.
+pub(super) async fn store(
+    state: &SequentialState,
+    instance_id: Option<&InstanceId>,
+    req: &UnpackedRequest,
+    response: &Response<Full<Bytes>>,
+) {
+    // Full bodies are a single in-memory chunk, so this never waits.
+    let Ok(body) = response.body().clone().collect().await;
+    let rule = rule(req, response, body.to_bytes());
+    let patterns = match rule.compile() {
+        Ok(patterns) => patterns,
+        Err(reason) => {
+            warn!(%reason, "Cannot record response");
+            return;
+        }
+    };
+    let key = RecordingKey::of(req);
+
+    let mut instances = state.instances.write().await;
+    let Some(instance) = state.find_instance(&mut instances, instance_id) else {
+        warn!("No instance to record the response into");
+        return;
+    };
+    if instance
+        .mocks
+        .iter()
+        .any(|m| m.recorded.as_ref() == Some(&key))
+    {
+        return;
+    }
+    let id = MockId(uuid7::uuid7().to_string());
+    info!(mock = %id, method = %key.method, path = %key.path, "Recorded response");
+    instance.mocks.push(InstalledMock {
+        id,
+        rule,
+        patterns,
+        uses: 0,
+        recorded: Some(key),
+    });
+    instance.mocks.sort_by_key(|m| m.rule.priority());
+    instance.mocks.reverse();
+}
+
+/// A rule matching the request's method, path, query and body that answers
+/// with the upstream's response.
+fn rule(req: &UnpackedRequest, response: &Response<Full<Bytes>>, body: Bytes) -> MockRule {
+    let json_body = match req.body.is_empty() {
+        true => None,
+        false => serde_json::from_slice(&req.body).ok().map(JsonBody::Exact),
+    };
+    let form_data = match json_body {
+        Some(_) => vec![],
+        None => form_urlencoded::parse(&req.body).into_owned().collect(),
+    };
+    let query = form_urlencoded::parse(req.uri.query().unwrap_or_default().as_bytes())
+        .into_owned()
+        .map(|(name, value)| (name, QueryValue::Exact(value)))
+        .collect();
+    let headers = response
+        .headers()
+        .iter()
+        .filter(|(name, _)| ![CONTENT_LENGTH, TRANSFER_ENCODING, CONNECTION].contains(name))
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect();
+    MockRule {
+        when: WhenRules {
+            match_path: PathMatcher::Exact(req.uri.path().to_string()),
+            form_data,
+            method: Some(method(&req.method)),
+            headers: vec![],
+            query,
+            exact_query: true,
+            json_body,
+            json_predicates: vec![],
+            scenario: None,
+        },
+        then: ThenState {
+            status: response.status().as_u16(),
+            headers,
+            body: body.to_vec(),
+            delay: None,
+            dribble: None,
+            fault: None,
+            transition_to: None,
+            responses: vec![],
+            sequence: SequencePolicy::default(),
+            templated: false,
+            passthrough: None,
+        },
+        times: None,
+    }
+}
+
+fn method(method: &hyper::Method) -> Method {
+    match *method {
+        hyper::Method::GET => Method::GET,
+        hyper::Method::POST => Method::POST,
+        hyper::Method::DELETE => Method::DELETE,
+        hyper::Method::PUT => Method::PUT,
+        hyper::Method::PATCH => Method::PATCH,
+        hyper::Method::HEAD => Method::HEAD,
+        hyper::Method::OPTIONS => Method::OPTIONS,
+        hyper::Method::CONNECT => Method::CONNECT,
+        hyper::Method::TRACE => Method::TRACE,
+        _ => Method::Custom(method.to_string()),
+    }
+}
@@ -0,0 +1,41 @@
+use hyper::Uri;
+
+use crate::interchange::InstanceId;
+
+use super::UnpackedRequest;
+
+/// Names the instance a request is for when the URL can't carry the prefix.
+pub const INSTANCE_HEADER: &str = "x-pulcinella-instance";
+
+const INSTANCE_PREFIX: &str = "/instance/";
+
+/// The base URL an isolated instance is reached on, under the mock port's
+/// `origin`.
+pub(super) fn instance_url(origin: &str, instance: &InstanceId) -> String {
+    format!("{origin}{INSTANCE_PREFIX}{}", instance.0)
+}
+
+/// Works out which instance a request is for from its `/instance/{id}` prefix,
+/// falling back to the instance header. The prefix is stripped so mocks match
+/// the rest of the path, and the header is dropped so it isn't proxied.
+pub(super) fn take_instance_id(req: &mut UnpackedRequest) -> Option<InstanceId> {
+    let header = req
+        .headers
+        .remove(INSTANCE_HEADER)
+        .and_then(|value| value.to_str().ok().map(String::from));
+    let Some(rest) = req.uri.path().strip_prefix(INSTANCE_PREFIX) else {
+        return header.map(InstanceId);
+    };
+
+    let (id, path) = match rest.find('/') {
+        Some(index) => rest.split_at(index),
+        None => (rest, "/"),
+    };
+    let path_and_query = match req.uri.query() {
+        Some(query) => format!("{path}?{query}"),
+        None => path.to_string(),
+    };
+    let id = InstanceId(id.to_string());
+    req.uri = Uri::builder().path_and_query(path_and_query).build().ok()?;
+    Some(id)
+}
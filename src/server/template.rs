@@ -0,0 +1,127 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hyper::header::HeaderValue;
+use serde_json::Value;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tracing::warn;
+
+use crate::interchange::{CannedResponse, JsonSelector};
+
+use super::{json, UnpackedRequest};
+
+/// Fills `{{...}}` placeholders in a response's body and header values from
+/// the request it answers:
+///
+/// - `{{method}}`, `{{path}}`
+/// - `{{path.<name>}}` for a template capture, `{{path.<n>}}` for the nth segment
+/// - `{{query.<name>}}`, `{{header.<name>}}`, `{{form.<name>}}`
+/// - `{{json.<path>}}` for a JSONPath into the body, e.g. `{{json.items[0].sku}}`
+/// - `{{uuid}}`, `{{now}}` (RFC 3339), `{{timestamp}}` (Unix millis) and
+///   `{{counter}}`, the number of requests the mock has answered
+///
+/// Placeholders that don't resolve render empty; bodies that aren't UTF-8
+/// are left alone, and headers whose rendered value isn't a valid header
+/// value, e.g. because it holds a newline, are dropped.
+pub(super) fn render_response(
+    response: CannedResponse,
+    req: &UnpackedRequest,
+    counter: u32,
+) -> CannedResponse {
+    let context = Context { req, counter };
+    let body = match String::from_utf8(response.body) {
+        Ok(body) => render(&body, &context).into_bytes(),
+        Err(err) => err.into_bytes(),
+    };
+    CannedResponse {
+        status: response.status,
+        headers: response
+            .headers
+            .into_iter()
+            .filter_map(|(name, value)| {
+                let value = render(&value, &context);
+                if HeaderValue::from_str(&value).is_err() {
+                    warn!(%name, ?value, "Dropping header with an invalid rendered value");
+                    return None;
+                }
+                Some((name, value))
+            })
+            .collect(),
+        body,
+    }
+}
+
+struct Context<'a> {
+    req: &'a UnpackedRequest,
+    counter: u32,
+}
+
+fn render(template: &str, context: &Context) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+        rendered.push_str(&rest[..start]);
+        let expression = rest[start + 2..start + end].trim();
+        rendered.push_str(&resolve(expression, context).unwrap_or_default());
+        rest = &rest[start + end + 2..];
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+fn resolve(expression: &str, context: &Context) -> Option<String> {
+    let req = context.req;
+    let (source, key) = match expression.split_once('.') {
+        Some((source, key)) => (source, Some(key)),
+        None => (expression, None),
+    };
+    match (source, key) {
+        ("method", None) => Some(req.method.to_string()),
+        ("path", None) => Some(req.uri.path().to_string()),
+        ("path", Some(key)) => path_segment(req, key),
+        ("query", Some(key)) => {
+            form_urlencoded::parse(req.uri.query().unwrap_or_default().as_bytes())
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value.into_owned())
+        }
+        ("header", Some(key)) => req
+            .headers
+            .get(key)
+            .and_then(|value| value.to_str().ok())
+            .map(String::from),
+        ("form", Some(key)) => form_urlencoded::parse(&req.body)
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.into_owned()),
+        ("json", Some(key)) => {
+            let body = serde_json::from_slice::<Value>(&req.body).ok()?;
+            let selector = JsonSelector::Path(format!("$.{key}"));
+            match json::select(&selector, &body).ok()?? {
+                Value::String(value) => Some(value.clone()),
+                value => Some(value.to_string()),
+            }
+        }
+        ("uuid", None) => Some(uuid7::uuid7().to_string()),
+        ("now", None) => OffsetDateTime::now_utc().format(&Rfc3339).ok(),
+        ("timestamp", None) => SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .ok()
+            .map(|now| now.as_millis().to_string()),
+        ("counter", None) => Some(context.counter.to_string()),
+        _ => None,
+    }
+}
+
+fn path_segment(req: &UnpackedRequest, key: &str) -> Option<String> {
+    if let Some((_, value)) = req.path_params.iter().find(|(name, _)| name == key) {
+        return Some(value.clone());
+    }
+    let index = key.parse::<usize>().ok()?;
+    req.uri
+        .path()
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .nth(index)
+        .map(String::from)
+}
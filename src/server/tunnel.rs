@@ -0,0 +1,46 @@
+use std::convert::Infallible;
+
+use http_body_util::{BodyExt, Empty};
+use hyper::{body::Incoming, upgrade, Request, Response};
+use hyper_util::rt::TokioIo;
+use tokio::net::TcpStream;
+use tracing::{info, trace, warn};
+
+use super::MockBody;
+
+/// Answers a `CONNECT host:port` by opening a connection to the target and,
+/// once the client's connection is handed over, copying raw bytes both ways
+/// until either side closes. Used for HTTPS through the forward proxy.
+pub(super) async fn connect(mut req: Request<Incoming>) -> Result<Response<MockBody>, Infallible> {
+    let Some(authority) = req.uri().authority().map(|a| a.to_string()) else {
+        return Ok(status(400));
+    };
+    let mut upstream = match TcpStream::connect(&authority).await {
+        Ok(upstream) => upstream,
+        Err(err) => {
+            warn!(%authority, ?err, "Cannot open tunnel");
+            return Ok(status(502));
+        }
+    };
+    info!(%authority, "Tunnelling connection");
+
+    tokio::task::spawn(async move {
+        match upgrade::on(&mut req).await {
+            Ok(upgraded) => {
+                let mut client = TokioIo::new(upgraded);
+                if let Err(err) = tokio::io::copy_bidirectional(&mut client, &mut upstream).await {
+                    trace!(%authority, ?err, "Tunnel closed");
+                }
+            }
+            Err(err) => warn!(%authority, ?err, "Cannot upgrade tunnelled connection"),
+        }
+    });
+    Ok(status(200))
+}
+
+pub(super) fn status(status: u16) -> Response<MockBody> {
+    Response::builder()
+        .status(status)
+        .body(Empty::new().boxed())
+        .unwrap()
+}
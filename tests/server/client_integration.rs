@@ -1,11 +1,13 @@
 use fake::{Fake, Faker};
-
-use pulcinella::{
-    client::{Client, ClientError, Method},
-    server::Mode,
+use serde_json::json;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
 };
 
-use crate::helpers::{start_server, ServerPorts};
+use pulcinella::client::{Client, ClientError, JsonCondition, Method};
+
+use crate::helpers::{setup_server, Dsl};
 
 #[tokio::test]
 async fn should_respond_with_404_when_no_mocks_specified() {
@@ -15,7 +17,7 @@ async fn should_respond_with_404_when_no_mocks_specified() {
         ..
     } = setup_server().await;
     let response = client
-        .get(&format!("{}/non-existent-path", mock_client.url()))
+        .get(format!("{}/non-existent-path", mock_client.url()))
         .send()
         .await
         .expect("Failed to send request");
@@ -39,7 +41,7 @@ async fn should_respond_with_200_for_matched_path() {
         .expect("Failed to install mock");
 
     let client = client
-        .get(&format!("{}{}", mock_client.url(), path))
+        .get(format!("{}{}", mock_client.url(), path))
         .send()
         .await
         .expect("Failed to send request");
@@ -68,7 +70,7 @@ async fn should_respond_with_404_for_unmatched_path() {
         .expect("Failed to install mock");
 
     let client = client
-        .get(&format!("{}{}", mock_client.url(), client_path))
+        .get(format!("{}{}", mock_client.url(), client_path))
         .send()
         .await
         .expect("Failed to send request");
@@ -93,7 +95,7 @@ async fn should_respond_with_another_status_code_for_matched_path() {
         .expect("Failed to install mock");
 
     let client = client
-        .get(&format!("{}{}", mock_client.url(), server_path))
+        .get(format!("{}{}", mock_client.url(), server_path))
         .send()
         .await
         .expect("Failed to send request");
@@ -101,6 +103,202 @@ async fn should_respond_with_another_status_code_for_matched_path() {
     assert_eq!(client.status(), 201);
 }
 
+#[tokio::test]
+async fn should_respond_for_path_matching_regex() {
+    let Dsl {
+        control: mock_client,
+        reqwest_client: client,
+        ..
+    } = setup_server().await;
+    mock_client
+        .when(|when| when.path_regex("/users/[0-9]+/orders"))
+        .then(|then| then.status(200))
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    let matched = client
+        .get(format!("{}/users/123/orders", mock_client.url()))
+        .send()
+        .await
+        .expect("Failed to send request");
+    let unmatched = client
+        .get(format!("{}/users/abc/orders", mock_client.url()))
+        .send()
+        .await
+        .expect("Failed to send request");
+    let partial = client
+        .get(format!("{}/api/users/123/orders", mock_client.url()))
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    assert_eq!(matched.status(), 200);
+    assert_eq!(unmatched.status(), 404);
+    assert_eq!(partial.status(), 404);
+}
+
+#[tokio::test]
+async fn should_respond_for_path_matching_glob() {
+    let Dsl {
+        control: mock_client,
+        reqwest_client: client,
+        ..
+    } = setup_server().await;
+    mock_client
+        .when(|when| when.path_glob("/users/*/orders"))
+        .then(|then| then.status(200))
+        .send()
+        .await
+        .expect("Failed to install mock");
+    mock_client
+        .when(|when| when.path_glob("/files/**"))
+        .then(|then| then.status(201))
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    let single_segment = client
+        .get(format!("{}/users/123/orders", mock_client.url()))
+        .send()
+        .await
+        .expect("Failed to send request");
+    let too_many_segments = client
+        .get(format!("{}/users/1/2/orders", mock_client.url()))
+        .send()
+        .await
+        .expect("Failed to send request");
+    let nested = client
+        .get(format!("{}/files/a/b/c.txt", mock_client.url()))
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    assert_eq!(single_segment.status(), 200);
+    assert_eq!(too_many_segments.status(), 404);
+    assert_eq!(nested.status(), 201);
+}
+
+#[tokio::test]
+async fn should_prefer_exact_path_over_pattern() {
+    let Dsl {
+        control: mock_client,
+        reqwest_client: client,
+        ..
+    } = setup_server().await;
+    mock_client
+        .when(|when| when.path("/users/me/orders"))
+        .then(|then| then.status(200))
+        .send()
+        .await
+        .expect("Failed to install mock");
+    mock_client
+        .when(|when| when.path_glob("/users/*/orders").method(Method::GET))
+        .then(|then| then.status(201))
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    let exact = client
+        .get(format!("{}/users/me/orders", mock_client.url()))
+        .send()
+        .await
+        .expect("Failed to send request");
+    let pattern = client
+        .get(format!("{}/users/123/orders", mock_client.url()))
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    assert_eq!(exact.status(), 200);
+    assert_eq!(pattern.status(), 201);
+}
+
+#[tokio::test]
+async fn should_capture_path_template_parameters() {
+    let Dsl {
+        control: mock_client,
+        reqwest_client: client,
+        ..
+    } = setup_server().await;
+    mock_client
+        .when(|when| when.path_template("/users/{id}/orders/{orderId}"))
+        .then(|then| then.status(200))
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    let matched = client
+        .get(format!("{}/users/123/orders/abc", mock_client.url()))
+        .send()
+        .await
+        .expect("Failed to send request");
+    let unmatched = client
+        .get(format!("{}/users/123/orders", mock_client.url()))
+        .send()
+        .await
+        .expect("Failed to send request");
+    let requests = mock_client
+        .received_requests()
+        .await
+        .expect("Failed to fetch requests");
+
+    assert_eq!(matched.status(), 200);
+    assert_eq!(unmatched.status(), 404);
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].path, "/users/123/orders/abc");
+    assert_eq!(requests[0].path_param("id"), Some("123"));
+    assert_eq!(requests[0].path_param("orderId"), Some("abc"));
+    assert!(requests[1].path_params.is_empty());
+}
+
+#[tokio::test]
+async fn should_prefer_literal_template_segments() {
+    let Dsl {
+        control: mock_client,
+        reqwest_client: client,
+        ..
+    } = setup_server().await;
+    mock_client
+        .when(|when| when.path_template("/users/{id}/orders"))
+        .then(|then| then.status(200))
+        .send()
+        .await
+        .expect("Failed to install mock");
+    mock_client
+        .when(|when| when.path_template("/users/{id}/{collection}"))
+        .then(|then| then.status(201))
+        .send()
+        .await
+        .expect("Failed to install mock");
+    mock_client
+        .when(|when| when.path_glob("/users/**"))
+        .then(|then| then.status(202))
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    let literal = client
+        .get(format!("{}/users/123/orders", mock_client.url()))
+        .send()
+        .await
+        .expect("Failed to send request");
+    let variable = client
+        .get(format!("{}/users/123/invoices", mock_client.url()))
+        .send()
+        .await
+        .expect("Failed to send request");
+    let pattern = client
+        .get(format!("{}/users/123/orders/abc", mock_client.url()))
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    assert_eq!(literal.status(), 200);
+    assert_eq!(variable.status(), 201);
+    assert_eq!(pattern.status(), 202);
+}
+
 #[tokio::test]
 async fn should_respond_with_404_for_matched_path_and_unmatched_form_data() {
     let form_name: String = Faker.fake();
@@ -126,7 +324,7 @@ async fn should_respond_with_404_for_matched_path_and_unmatched_form_data() {
         .await
         .expect("Failed to install mock");
     let client = client
-        .get(&format!("{}{}", mock_client.url(), server_path))
+        .get(format!("{}{}", mock_client.url(), server_path))
         .send()
         .await
         .expect("Failed to send request");
@@ -153,7 +351,7 @@ async fn should_respond_with_404_for_unmatched_method() {
         .await
         .expect("Failed to install mock");
     let client = client
-        .get(&format!("{}{}", mock_client.url(), server_path))
+        .get(format!("{}{}", mock_client.url(), server_path))
         .send()
         .await
         .expect("Failed to send request");
@@ -162,6 +360,98 @@ async fn should_respond_with_404_for_unmatched_method() {
 }
 
 
+#[tokio::test]
+async fn should_respond_for_patch_and_extension_methods() {
+    let server_path = format!("/{}", Faker.fake::<String>());
+    let Dsl {
+        control: mock_client,
+        reqwest_client: client,
+        ..
+    } = setup_server().await;
+
+    mock_client
+        .when(|when| when.path(&server_path).method(Method::PATCH))
+        .then(|then| then.status(204))
+        .send()
+        .await
+        .expect("Failed to install mock");
+    mock_client
+        .when(|when| {
+            when.path(&server_path)
+                .method(Method::Custom("PURGE".to_string()))
+        })
+        .then(|then| then.status(202))
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    let patch = client
+        .patch(format!("{}{}", mock_client.url(), server_path))
+        .send()
+        .await
+        .expect("Failed to send request");
+    let purge = client
+        .request(
+            reqwest::Method::from_bytes(b"PURGE").unwrap(),
+            format!("{}{}", mock_client.url(), server_path),
+        )
+        .send()
+        .await
+        .expect("Failed to send request");
+    let put = client
+        .put(format!("{}{}", mock_client.url(), server_path))
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    assert_eq!(patch.status(), 204);
+    assert_eq!(purge.status(), 202);
+    assert_eq!(put.status(), 404);
+}
+
+#[tokio::test]
+async fn should_respond_to_head_with_headers_and_no_body() {
+    let server_path = format!("/{}", Faker.fake::<String>());
+    let Dsl {
+        control: mock_client,
+        server_ports,
+        ..
+    } = setup_server().await;
+
+    mock_client
+        .when(|when| when.path(&server_path).method(Method::HEAD))
+        .then(|then| then.status(200).header("etag", "v1").body("hello"))
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    // reqwest never reads a HEAD body, so check the wire directly.
+    let mut stream = TcpStream::connect(("127.0.0.1", server_ports.mock))
+        .await
+        .expect("Failed to connect");
+    stream
+        .write_all(
+            format!("HEAD {server_path} HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n")
+                .as_bytes(),
+        )
+        .await
+        .expect("Failed to send request");
+    let mut reply = vec![];
+    stream
+        .read_to_end(&mut reply)
+        .await
+        .expect("Failed to read response");
+    let reply = String::from_utf8(reply).unwrap();
+    let (head, body) = reply
+        .split_once("\r\n\r\n")
+        .expect("Response should end its headers");
+
+    assert!(head.starts_with("HTTP/1.1 200"), "{head}");
+    assert!(head.contains("content-length: 5"), "{head}");
+    assert!(head.contains("etag: v1"), "{head}");
+    assert_eq!("", body);
+}
+
 #[tokio::test]
 async fn should_respond_with_the_most_specific_mock() {
     let form_name: String = Faker.fake();
@@ -195,7 +485,7 @@ async fn should_respond_with_the_most_specific_mock() {
         .expect("Failed to install mock");
 
     let client = client
-        .post(&format!("{}{}", mock_client.url(), server_path))
+        .post(format!("{}{}", mock_client.url(), server_path))
         .form(&[(form_name, form_value), (form_name2, form_value2)])
         .send()
         .await
@@ -204,6 +494,356 @@ async fn should_respond_with_the_most_specific_mock() {
     assert_eq!(client.status(), 201);
 }
 
+#[tokio::test]
+async fn should_respond_with_200_for_matched_header() {
+    let header_name = Faker.fake::<String>();
+    let header_value = Faker.fake::<String>();
+    let server_path = format!("/{}", Faker.fake::<String>());
+    let Dsl {
+        control: mock_client,
+        reqwest_client: client,
+        ..
+    } = setup_server().await;
+
+    mock_client
+        .when(|when| when.path(&server_path).header(&header_name, &header_value))
+        .then(|then| then.status(200))
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    let client = client
+        .get(format!("{}{}", mock_client.url(), server_path))
+        .header(header_name, header_value)
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    assert_eq!(client.status(), 200);
+}
+
+#[tokio::test]
+async fn should_respond_with_404_for_unmatched_header() {
+    let header_name = Faker.fake::<String>();
+    let header_value = Faker.fake::<String>();
+    let server_path = format!("/{}", Faker.fake::<String>());
+    let Dsl {
+        control: mock_client,
+        reqwest_client: client,
+        ..
+    } = setup_server().await;
+
+    mock_client
+        .when(|when| when.path(&server_path).header(&header_name, &header_value))
+        .then(|then| then.status(200))
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    let client = client
+        .get(format!("{}{}", mock_client.url(), server_path))
+        .header(header_name, format!("{}-other", header_value))
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    assert_eq!(client.status(), 404);
+}
+
+#[tokio::test]
+async fn should_prefer_mock_with_matching_header() {
+    let server_path = format!("/{}", Faker.fake::<String>());
+    let Dsl {
+        control: mock_client,
+        reqwest_client: client,
+        ..
+    } = setup_server().await;
+
+    mock_client
+        .when(|when| when.path(&server_path))
+        .then(|then| then.status(200))
+        .send()
+        .await
+        .expect("Failed to install mock");
+    mock_client
+        .when(|when| {
+            when.path(&server_path)
+                .header("Authorization", "Bearer token")
+        })
+        .then(|then| then.status(201))
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    let authorized = client
+        .get(format!("{}{}", mock_client.url(), server_path))
+        .header("authorization", "Bearer token")
+        .send()
+        .await
+        .expect("Failed to send request");
+    let anonymous = client
+        .get(format!("{}{}", mock_client.url(), server_path))
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    assert_eq!(authorized.status(), 201);
+    assert_eq!(anonymous.status(), 200);
+}
+
+#[tokio::test]
+async fn should_respond_by_query_parameter_value() {
+    let server_path = format!("/{}", Faker.fake::<String>());
+    let Dsl {
+        control: mock_client,
+        reqwest_client: client,
+        ..
+    } = setup_server().await;
+
+    mock_client
+        .when(|when| when.path(&server_path).query("q", "foo"))
+        .then(|then| then.status(200).body("foo"))
+        .send()
+        .await
+        .expect("Failed to install mock");
+    mock_client
+        .when(|when| when.path(&server_path).query("q", "bar"))
+        .then(|then| then.status(200).body("bar"))
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    let foo = client
+        .get(format!("{}{}?q=foo", mock_client.url(), server_path))
+        .send()
+        .await
+        .expect("Failed to send request");
+    let bar = client
+        .get(format!("{}{}?page=2&q=bar", mock_client.url(), server_path))
+        .send()
+        .await
+        .expect("Failed to send request");
+    let baz = client
+        .get(format!("{}{}?q=baz", mock_client.url(), server_path))
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    assert_eq!("foo", foo.text().await.unwrap());
+    assert_eq!("bar", bar.text().await.unwrap());
+    assert_eq!(baz.status(), 404);
+}
+
+#[tokio::test]
+async fn should_match_present_and_any_query_parameters() {
+    let server_path = format!("/{}", Faker.fake::<String>());
+    let Dsl {
+        control: mock_client,
+        reqwest_client: client,
+        ..
+    } = setup_server().await;
+
+    mock_client
+        .when(|when| when.path(&server_path).query_present("debug"))
+        .then(|then| then.status(201))
+        .send()
+        .await
+        .expect("Failed to install mock");
+    mock_client
+        .when(|when| when.path(&server_path).query_any("page"))
+        .then(|then| then.status(202))
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    let flag = client
+        .get(format!("{}{}?debug", mock_client.url(), server_path))
+        .send()
+        .await
+        .expect("Failed to send request");
+    let flag_with_value = client
+        .get(format!("{}{}?debug=1", mock_client.url(), server_path))
+        .send()
+        .await
+        .expect("Failed to send request");
+    let any = client
+        .get(format!("{}{}?page=7", mock_client.url(), server_path))
+        .send()
+        .await
+        .expect("Failed to send request");
+    let missing = client
+        .get(format!("{}{}", mock_client.url(), server_path))
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    assert_eq!(flag.status(), 201);
+    assert_eq!(flag_with_value.status(), 404);
+    assert_eq!(any.status(), 202);
+    assert_eq!(missing.status(), 404);
+}
+
+#[tokio::test]
+async fn should_match_exact_json_body_regardless_of_key_order() {
+    let server_path = format!("/{}", Faker.fake::<String>());
+    let Dsl {
+        control: mock_client,
+        reqwest_client: client,
+        ..
+    } = setup_server().await;
+
+    mock_client
+        .when(|when| {
+            when.path(&server_path)
+                .json_body(json!({"name": "pulcinella", "tags": ["a", "b"]}))
+        })
+        .then(|then| then.status(201))
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    let matched = client
+        .post(format!("{}{}", mock_client.url(), server_path))
+        .body(r#"{ "tags": ["a", "b"],   "name": "pulcinella" }"#)
+        .send()
+        .await
+        .expect("Failed to send request");
+    let extra_key = client
+        .post(format!("{}{}", mock_client.url(), server_path))
+        .json(&json!({"name": "pulcinella", "tags": ["a", "b"], "extra": true}))
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    assert_eq!(matched.status(), 201);
+    assert_eq!(extra_key.status(), 404);
+}
+
+#[tokio::test]
+async fn should_match_partial_json_body() {
+    let server_path = format!("/{}", Faker.fake::<String>());
+    let Dsl {
+        control: mock_client,
+        reqwest_client: client,
+        ..
+    } = setup_server().await;
+
+    mock_client
+        .when(|when| {
+            when.path(&server_path)
+                .json_body_partial(json!({"order": {"items": [{"sku": "X"}]}}))
+        })
+        .then(|then| then.status(201))
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    let superset = client
+        .post(format!("{}{}", mock_client.url(), server_path))
+        .json(&json!({
+            "id": 7,
+            "order": {"items": [{"sku": "Y", "qty": 1}, {"sku": "X", "qty": 2}]}
+        }))
+        .send()
+        .await
+        .expect("Failed to send request");
+    let missing = client
+        .post(format!("{}{}", mock_client.url(), server_path))
+        .json(&json!({"order": {"items": [{"sku": "Y"}]}}))
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    assert_eq!(superset.status(), 201);
+    assert_eq!(missing.status(), 404);
+}
+
+#[tokio::test]
+async fn should_match_json_path_and_pointer_predicates() {
+    let server_path = format!("/{}", Faker.fake::<String>());
+    let Dsl {
+        control: mock_client,
+        reqwest_client: client,
+        ..
+    } = setup_server().await;
+
+    mock_client
+        .when(|when| {
+            when.path(&server_path)
+                .json_path("$.order.items[0].sku", JsonCondition::Equals(json!("X")))
+                .json_pointer("/order/id", JsonCondition::Exists)
+                .json_path(
+                    "$.order['customer']",
+                    JsonCondition::Matches("^cus_[0-9]+$".into()),
+                )
+        })
+        .then(|then| then.status(201))
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    let matched = client
+        .post(format!("{}{}", mock_client.url(), server_path))
+        .json(&json!({"order": {"id": 1, "customer": "cus_42", "items": [{"sku": "X"}]}}))
+        .send()
+        .await
+        .expect("Failed to send request");
+    let wrong_sku = client
+        .post(format!("{}{}", mock_client.url(), server_path))
+        .json(&json!({"order": {"id": 1, "customer": "cus_42", "items": [{"sku": "Y"}]}}))
+        .send()
+        .await
+        .expect("Failed to send request");
+    let missing_id = client
+        .post(format!("{}{}", mock_client.url(), server_path))
+        .json(&json!({"order": {"customer": "cus_42", "items": [{"sku": "X"}]}}))
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    assert_eq!(matched.status(), 201);
+    assert_eq!(wrong_sku.status(), 404);
+    assert_eq!(missing_id.status(), 404);
+}
+
+#[tokio::test]
+async fn should_fail_to_install_mock_with_invalid_json_predicate() {
+    let Dsl {
+        control: mock_client,
+        ..
+    } = setup_server().await;
+
+    let result = mock_client
+        .when(|when| when.path("/").json_path("order.id", JsonCondition::Exists))
+        .then(|then| then.status(200))
+        .send()
+        .await;
+
+    assert_eq!(result.err(), Some(ClientError::FailedToInstallMockRule));
+}
+
+#[tokio::test]
+async fn should_fail_to_install_mock_with_invalid_json_pointer() {
+    let Dsl {
+        control: mock_client,
+        ..
+    } = setup_server().await;
+
+    for pointer in ["order/id", "/order/~2id", "/order~"] {
+        let result = mock_client
+            .when(|when| when.path("/").json_pointer(pointer, JsonCondition::Exists))
+            .then(|then| then.status(200))
+            .send()
+            .await;
+
+        assert_eq!(
+            result.err(),
+            Some(ClientError::FailedToInstallMockRule),
+            "{pointer}"
+        );
+    }
+}
+
 #[tokio::test]
 async fn should_respond_with_headers() {
     let header_name = Faker.fake::<String>();
@@ -223,7 +863,7 @@ async fn should_respond_with_headers() {
         .expect("Failed to install mock");
 
     let client = client
-        .get(&format!("{}{}", mock_client.url(), server_path))
+        .get(format!("{}{}", mock_client.url(), server_path))
         .send()
         .await
         .expect("Failed to send request");
@@ -250,7 +890,7 @@ async fn should_respond_with_a_body() {
         .expect("Failed to install mock");
 
     let client = client
-        .get(&format!("{}{}", mock_client.url(), server_path))
+        .get(format!("{}{}", mock_client.url(), server_path))
         .send()
         .await
         .expect("Failed to send request");
@@ -259,6 +899,67 @@ async fn should_respond_with_a_body() {
     assert_eq!(body, client.text().await.unwrap());
 }
 
+#[tokio::test]
+async fn should_delete_a_single_mock() {
+    let deleted_path = format!("/{}", Faker.fake::<String>());
+    let kept_path = format!("/{}-kept", Faker.fake::<String>());
+    let Dsl {
+        control: mock_client,
+        reqwest_client: client,
+        ..
+    } = setup_server().await;
+    let deleted = mock_client
+        .when(|when| when.path(&deleted_path))
+        .then(|then| then.status(200))
+        .send()
+        .await
+        .expect("Failed to install mock");
+    let kept = mock_client
+        .when(|when| when.path(&kept_path))
+        .then(|then| then.status(201))
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    assert_ne!(deleted.id(), kept.id());
+    assert_eq!(kept.rule().then.status, 201);
+    deleted.delete().await.expect("Failed to delete mock");
+
+    let deleted_response = client
+        .get(format!("{}{}", mock_client.url(), deleted_path))
+        .send()
+        .await
+        .expect("Failed to send request");
+    let kept_response = client
+        .get(format!("{}{}", mock_client.url(), kept_path))
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    assert_eq!(deleted_response.status(), 404);
+    assert_eq!(kept_response.status(), 201);
+}
+
+#[tokio::test]
+async fn should_fail_to_delete_mock_from_replaced_instance() {
+    let Dsl {
+        control: old_mock_client,
+        server_ports,
+        ..
+    } = setup_server().await;
+    let mock = old_mock_client
+        .when(|when| when.path("/"))
+        .then(|then| then.status(200))
+        .send()
+        .await
+        .expect("Failed to install mock");
+    let _new_mock_client = Client::new(&format!("http://localhost:{}", server_ports.control_plane))
+        .await
+        .expect("mock client failed to start");
+
+    assert_eq!(mock.delete().await, Err(ClientError::InstanceNoLongerValid));
+}
+
 #[tokio::test]
 async fn should_fail_if_reusing_an_old_client() {
     let Dsl {
@@ -275,7 +976,7 @@ async fn should_fail_if_reusing_an_old_client() {
         .then(|then| then.status(200))
         .send()
         .await;
-    assert_eq!(result, Err(ClientError::InstanceNoLongerValid));
+    assert_eq!(result.err(), Some(ClientError::InstanceNoLongerValid));
 }
 
 #[tokio::test]
@@ -299,7 +1000,7 @@ async fn should_forget_old_mocks_when_new_client() {
         .expect("mock client failed to start");
 
     let response = client
-        .get(&format!("{}{}", old_mock_client.url(), server_path))
+        .get(format!("{}{}", old_mock_client.url(), server_path))
         .send()
         .await
         .expect("Failed to send request");
@@ -328,22 +1029,3 @@ fn assert_header(
             .as_bytes(),
     );
 }
-
-struct Dsl {
-    control: Client,
-    reqwest_client: reqwest::Client,
-    server_ports: ServerPorts,
-}
-
-async fn setup_server() -> Dsl {
-    let server_ports = start_server(Mode::Mock).await;
-    let mock_client = Client::new(&format!("http://localhost:{}", server_ports.control_plane))
-        .await
-        .expect("mock client failed to start");
-    let client = reqwest::Client::new();
-    Dsl {
-        control: mock_client,
-        reqwest_client: client,
-        server_ports,
-    }
-}
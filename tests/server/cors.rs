@@ -0,0 +1,129 @@
+use pulcinella::client::{CorsPolicy, Method};
+use reqwest::header::HeaderMap;
+
+use crate::helpers::{setup_server, Dsl};
+
+#[tokio::test]
+async fn should_let_a_mock_take_over_preflight() {
+    let Dsl {
+        control: mock_client,
+        reqwest_client: client,
+        ..
+    } = setup_server().await;
+    mock_client
+        .when(|when| when.path("/api").method(Method::OPTIONS))
+        .then(|then| then.status(403))
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    let response = client
+        .request(
+            reqwest::Method::OPTIONS,
+            format!("{}/api", mock_client.url()),
+        )
+        .header("origin", "http://frontend.test")
+        .header("access-control-request-method", "POST")
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    assert_eq!(403, response.status());
+    assert_eq!(None, allow_origin(response.headers()));
+}
+
+#[tokio::test]
+async fn should_not_answer_preflight_when_cors_disabled() {
+    let Dsl {
+        control: mock_client,
+        reqwest_client: client,
+        ..
+    } = setup_server().await;
+    mock_client
+        .configure_cors(CorsPolicy::Disabled)
+        .await
+        .expect("Failed to configure cors");
+    mock_client
+        .when(|when| when.path("/api"))
+        .then(|then| then.status(200))
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    let preflight = client
+        .request(
+            reqwest::Method::OPTIONS,
+            format!("{}/other", mock_client.url()),
+        )
+        .header("origin", "http://frontend.test")
+        .send()
+        .await
+        .expect("Failed to send request");
+    let response = client
+        .get(format!("{}/api", mock_client.url()))
+        .header("origin", "http://frontend.test")
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    assert_eq!(404, preflight.status());
+    assert_eq!(None, allow_origin(preflight.headers()));
+    assert_eq!(200, response.status());
+    assert_eq!(None, allow_origin(response.headers()));
+}
+
+#[tokio::test]
+async fn should_only_allow_configured_origins() {
+    let Dsl {
+        control: mock_client,
+        reqwest_client: client,
+        ..
+    } = setup_server().await;
+    mock_client
+        .configure_cors(CorsPolicy::Restricted {
+            allowed_origins: vec!["http://frontend.test".to_string()],
+            allowed_methods: vec!["GET".to_string(), "POST".to_string()],
+            allowed_headers: vec!["content-type".to_string()],
+        })
+        .await
+        .expect("Failed to configure cors");
+
+    let allowed = client
+        .request(
+            reqwest::Method::OPTIONS,
+            format!("{}/api", mock_client.url()),
+        )
+        .header("origin", "http://frontend.test")
+        .send()
+        .await
+        .expect("Failed to send request");
+    let rejected = client
+        .request(
+            reqwest::Method::OPTIONS,
+            format!("{}/api", mock_client.url()),
+        )
+        .header("origin", "http://evil.test")
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    assert_eq!(200, allowed.status());
+    assert_eq!(
+        Some("http://frontend.test"),
+        allow_origin(allowed.headers())
+    );
+    assert_eq!(
+        Some("GET, POST"),
+        allowed
+            .headers()
+            .get("access-control-allow-methods")
+            .and_then(|v| v.to_str().ok())
+    );
+    assert_eq!(None, allow_origin(rejected.headers()));
+}
+
+fn allow_origin(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get("access-control-allow-origin")
+        .and_then(|v| v.to_str().ok())
+}
@@ -0,0 +1,193 @@
+use std::time::{Duration, Instant};
+
+use pulcinella::client::{ClientError, Delay};
+
+use crate::helpers::{setup_server, Dsl};
+
+#[tokio::test]
+async fn should_delay_response() {
+    let Dsl {
+        control: mock_client,
+        reqwest_client: client,
+        ..
+    } = setup_server().await;
+    mock_client
+        .when(|when| when.path("/slow"))
+        .then(|then| then.status(200).delay(Duration::from_millis(200)))
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    let start = Instant::now();
+    let response = client
+        .get(format!("{}/slow", mock_client.url()))
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    assert_eq!(200, response.status());
+    assert!(start.elapsed() >= Duration::from_millis(200));
+}
+
+#[tokio::test]
+async fn should_delay_within_random_bounds() {
+    let Dsl {
+        control: mock_client,
+        reqwest_client: client,
+        ..
+    } = setup_server().await;
+    mock_client
+        .when(|when| when.path("/uniform"))
+        .then(|then| {
+            then.status(200).delay(Delay::Uniform {
+                min: Duration::from_millis(100),
+                max: Duration::from_millis(150),
+            })
+        })
+        .send()
+        .await
+        .expect("Failed to install mock");
+    mock_client
+        .when(|when| when.path("/log-normal"))
+        .then(|then| {
+            then.status(200).delay(Delay::LogNormal {
+                median: Duration::from_millis(10),
+                sigma: 2.0,
+                min: Duration::from_millis(100),
+                max: Duration::from_millis(150),
+            })
+        })
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    for path in ["uniform", "log-normal"] {
+        let start = Instant::now();
+        client
+            .get(format!("{}/{path}", mock_client.url()))
+            .send()
+            .await
+            .expect("Failed to send request");
+        let elapsed = start.elapsed();
+
+        assert!(
+            elapsed >= Duration::from_millis(100),
+            "{path} took {elapsed:?}"
+        );
+        assert!(elapsed < Duration::from_secs(1), "{path} took {elapsed:?}");
+    }
+}
+
+#[tokio::test]
+async fn should_dribble_body() {
+    let Dsl {
+        control: mock_client,
+        reqwest_client: client,
+        ..
+    } = setup_server().await;
+    mock_client
+        .when(|when| when.path("/dribble"))
+        .then(|then| {
+            then.status(200)
+                .body("abcdef")
+                .dribble(2, Duration::from_millis(100))
+        })
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    let start = Instant::now();
+    let response = client
+        .get(format!("{}/dribble", mock_client.url()))
+        .send()
+        .await
+        .expect("Failed to send request");
+    let chunked = response.headers().get("transfer-encoding").cloned();
+    let body = response.text().await.expect("Failed to read body");
+
+    assert_eq!("abcdef", body);
+    assert_eq!(
+        Some("chunked"),
+        chunked.as_ref().and_then(|v| v.to_str().ok())
+    );
+    assert!(start.elapsed() >= Duration::from_millis(200));
+}
+
+#[tokio::test]
+async fn should_clamp_log_normal_delay_with_a_long_tail() {
+    let Dsl {
+        control: mock_client,
+        reqwest_client: client,
+        ..
+    } = setup_server().await;
+    // About half the samples around this median don't fit in a `Duration`.
+    mock_client
+        .when(|when| when.path("/long-tail"))
+        .then(|then| {
+            then.status(200).delay(Delay::LogNormal {
+                median: Duration::from_secs(u64::MAX / 2),
+                sigma: 10.0,
+                min: Duration::ZERO,
+                max: Duration::from_millis(50),
+            })
+        })
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    for _ in 0..20 {
+        let start = Instant::now();
+        let response = client
+            .get(format!("{}/long-tail", mock_client.url()))
+            .send()
+            .await
+            .expect("Failed to send request");
+
+        assert_eq!(200, response.status());
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+}
+
+#[tokio::test]
+async fn should_reject_log_normal_delay_with_huge_sigma() {
+    let Dsl {
+        control: mock_client,
+        ..
+    } = setup_server().await;
+
+    let result = mock_client
+        .when(|when| when.path("/log-normal"))
+        .then(|then| {
+            then.status(200).delay(Delay::LogNormal {
+                median: Duration::from_millis(10),
+                sigma: 500.0,
+                min: Duration::ZERO,
+                max: Duration::from_millis(50),
+            })
+        })
+        .send()
+        .await;
+
+    assert_eq!(Some(ClientError::FailedToInstallMockRule), result.err());
+}
+
+#[tokio::test]
+async fn should_reject_delay_with_inverted_bounds() {
+    let Dsl {
+        control: mock_client,
+        ..
+    } = setup_server().await;
+
+    let result = mock_client
+        .when(|when| when.path("/uniform"))
+        .then(|then| {
+            then.status(200).delay(Delay::Uniform {
+                min: Duration::from_millis(150),
+                max: Duration::from_millis(100),
+            })
+        })
+        .send()
+        .await;
+
+    assert_eq!(Some(ClientError::FailedToInstallMockRule), result.err());
+}
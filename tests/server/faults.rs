@@ -0,0 +1,83 @@
+use std::time::Duration;
+
+use pulcinella::{
+    client::{Client, Fault},
+    server::Mode,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+use crate::helpers::{start_server, ServerPorts};
+
+#[tokio::test]
+async fn should_reset_connection() {
+    let (server_ports, _mock_client) = setup_fault(Fault::ConnectionReset).await;
+
+    let reply = raw_request(&server_ports).await;
+
+    assert_eq!(
+        std::io::ErrorKind::ConnectionReset,
+        reply.expect_err("Expected a reset").kind()
+    );
+}
+
+#[tokio::test]
+async fn should_close_without_reply() {
+    let (server_ports, _mock_client) = setup_fault(Fault::EmptyReply).await;
+
+    let reply = raw_request(&server_ports).await;
+
+    assert_eq!(Vec::<u8>::new(), reply.expect("Failed to read reply"));
+}
+
+#[tokio::test]
+async fn should_send_garbage() {
+    let (server_ports, mock_client) = setup_fault(Fault::Garbage).await;
+
+    let reply = raw_request(&server_ports)
+        .await
+        .expect("Failed to read reply");
+    let response = reqwest::get(format!("{}/fault", mock_client.url())).await;
+
+    assert!(!reply.is_empty());
+    assert!(!reply.starts_with(b"HTTP/"));
+    assert!(response.is_err());
+}
+
+#[tokio::test]
+async fn should_send_headers_then_stall() {
+    let (_server_ports, mock_client) = setup_fault(Fault::HeadersThenStall).await;
+
+    let response = reqwest::get(format!("{}/fault", mock_client.url()))
+        .await
+        .expect("Failed to receive headers");
+    let body = tokio::time::timeout(Duration::from_millis(300), response.bytes()).await;
+
+    assert!(body.is_err(), "Expected the body to stall");
+}
+
+async fn setup_fault(fault: Fault) -> (ServerPorts, Client) {
+    let server_ports = start_server(Mode::Mock).await;
+    let mock_client = Client::new(&format!("http://localhost:{}", server_ports.control_plane))
+        .await
+        .expect("mock client failed to start");
+    mock_client
+        .when(|when| when.path("/fault"))
+        .then(|then| then.status(200).body("never sent").fault(fault))
+        .send()
+        .await
+        .expect("Failed to install mock");
+    (server_ports, mock_client)
+}
+
+async fn raw_request(server_ports: &ServerPorts) -> std::io::Result<Vec<u8>> {
+    let mut stream = TcpStream::connect(("127.0.0.1", server_ports.mock)).await?;
+    stream
+        .write_all(b"GET /fault HTTP/1.1\r\nhost: localhost\r\n\r\n")
+        .await?;
+    let mut reply = vec![];
+    stream.read_to_end(&mut reply).await?;
+    Ok(reply)
+}
@@ -1,6 +1,9 @@
 use std::net::SocketAddr;
 
-use pulcinella::server::{bind_socket, run_controlplane, run_mock, Mode, SequentialState};
+use pulcinella::{
+    client::Client,
+    server::{bind_socket, run_controlplane, run_mockplane, Mode, SequentialState},
+};
 
 pub(crate) struct ServerPorts {
     pub(crate) control_plane: u16,
@@ -8,6 +11,17 @@ pub(crate) struct ServerPorts {
 }
 
 pub(crate) async fn start_server(mode: Mode) -> ServerPorts {
+    start_server_with(mode, |state| state).await
+}
+
+pub(crate) async fn start_isolated_server(mode: Mode) -> ServerPorts {
+    start_server_with(mode, SequentialState::isolated).await
+}
+
+pub(crate) async fn start_server_with(
+    mode: Mode,
+    configure: impl FnOnce(SequentialState) -> SequentialState,
+) -> ServerPorts {
     let control_plane = bind_socket(SocketAddr::from(([127, 0, 0, 1], 0)))
         .await
         .unwrap();
@@ -15,11 +29,11 @@ pub(crate) async fn start_server(mode: Mode) -> ServerPorts {
         .await
         .unwrap();
 
-    let state = SequentialState::new(mock.port);
+    let state = configure(SequentialState::new(mock.port).with_mode(mode));
     let control_plane_server = run_controlplane(control_plane.listener, state.clone());
     tokio::spawn(control_plane_server);
 
-    let proxy_server = run_mock(mock.listener, state, mode);
+    let proxy_server = run_mockplane(mock.listener, state);
     tokio::spawn(proxy_server);
 
     ServerPorts {
@@ -27,3 +41,23 @@ pub(crate) async fn start_server(mode: Mode) -> ServerPorts {
         mock: mock.port,
     }
 }
+
+/// A mock server in [`Mode::Mock`] with a client to control it and one to
+/// send it requests.
+pub(crate) struct Dsl {
+    pub(crate) control: Client,
+    pub(crate) reqwest_client: reqwest::Client,
+    pub(crate) server_ports: ServerPorts,
+}
+
+pub(crate) async fn setup_server() -> Dsl {
+    let server_ports = start_server(Mode::Mock).await;
+    let mock_client = Client::new(&format!("http://localhost:{}", server_ports.control_plane))
+        .await
+        .expect("mock client failed to start");
+    Dsl {
+        control: mock_client,
+        reqwest_client: reqwest::Client::new(),
+        server_ports,
+    }
+}
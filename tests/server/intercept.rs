@@ -0,0 +1,158 @@
+use std::{convert::Infallible, net::SocketAddr, sync::Arc};
+
+use http_body_util::Full;
+use hyper::{body::Bytes, server::conn::http1, service::service_fn, Response};
+use hyper_util::rt::TokioIo;
+use pulcinella::{
+    client::{Client, Fault},
+    server::{bind_socket, CertificateAuthority, Interception, Mode},
+};
+use rustls::{crypto::ring, pki_types::PrivateKeyDer, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+
+use crate::helpers::start_server_with;
+
+#[tokio::test]
+async fn should_answer_intercepted_https_from_mocks() {
+    let authority = CertificateAuthority::generate().unwrap();
+    let trusted = authority.cert_pem().to_string();
+    let authority = CertificateAuthority::from_pem(authority.cert_pem(), &authority.key_pem())
+        .expect("Failed to load authority");
+    let server_ports = start_server_with(Mode::Proxy, |state| {
+        state.with_interception(Interception::new(authority))
+    })
+    .await;
+    let mock_client = Client::new(&format!("http://localhost:{}", server_ports.control_plane))
+        .await
+        .expect("mock client failed to start");
+    mock_client
+        .when(|when| when.path("/users"))
+        .then(|then| then.status(200).body("mocked"))
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    let response = https_client(server_ports.mock, &trusted)
+        .get("https://api.example.test/users")
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    assert_eq!(200, response.status());
+    assert_eq!("mocked", response.text().await.unwrap());
+}
+
+#[tokio::test]
+async fn should_inject_faults_into_intercepted_https() {
+    let authority = CertificateAuthority::generate().unwrap();
+    let trusted = authority.cert_pem().to_string();
+    let server_ports = start_server_with(Mode::Proxy, |state| {
+        state.with_interception(Interception::new(authority))
+    })
+    .await;
+    let mock_client = Client::new(&format!("http://localhost:{}", server_ports.control_plane))
+        .await
+        .expect("mock client failed to start");
+    mock_client
+        .when(|when| when.path("/users"))
+        .then(|then| {
+            then.status(200)
+                .body("never sent")
+                .fault(Fault::ConnectionReset)
+        })
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    let result = https_client(server_ports.mock, &trusted)
+        .get("https://api.example.test/users")
+        .send()
+        .await;
+
+    assert!(result.is_err(), "{result:?}");
+}
+
+#[tokio::test]
+async fn should_fall_back_to_trusted_self_signed_upstream() {
+    let (upstream_port, upstream_cert) = start_https_upstream().await;
+    let authority = CertificateAuthority::generate().unwrap();
+    let trusted = authority.cert_pem().to_string();
+    let interception = Interception::new(authority)
+        .trust_upstream(&upstream_cert)
+        .expect("Failed to trust upstream");
+    let server_ports =
+        start_server_with(Mode::Proxy, |state| state.with_interception(interception)).await;
+
+    let response = https_client(server_ports.mock, &trusted)
+        .get(format!("https://localhost:{upstream_port}/orders"))
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    assert_eq!(200, response.status());
+    assert_eq!("from upstream", response.text().await.unwrap());
+}
+
+#[tokio::test]
+async fn should_refuse_untrusted_upstream() {
+    let (upstream_port, _) = start_https_upstream().await;
+    let authority = CertificateAuthority::generate().unwrap();
+    let trusted = authority.cert_pem().to_string();
+    let server_ports = start_server_with(Mode::Proxy, |state| {
+        state.with_interception(Interception::new(authority))
+    })
+    .await;
+
+    let response = https_client(server_ports.mock, &trusted)
+        .get(format!("https://localhost:{upstream_port}/orders"))
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    assert_eq!(502, response.status());
+}
+
+fn https_client(proxy_port: u16, trusted_pem: &str) -> reqwest::Client {
+    let proxy = reqwest::Proxy::https(format!("http://localhost:{proxy_port}")).unwrap();
+    let trusted = reqwest::Certificate::from_pem(trusted_pem.as_bytes()).unwrap();
+    reqwest::ClientBuilder::new()
+        .proxy(proxy)
+        .add_root_certificate(trusted)
+        .build()
+        .unwrap()
+}
+
+async fn start_https_upstream() -> (u16, String) {
+    let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    let config = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_no_client_auth()
+        .with_single_cert(
+            vec![certified.cert.der().clone()],
+            PrivateKeyDer::Pkcs8(certified.key_pair.serialize_der().into()),
+        )
+        .unwrap();
+    let acceptor = TlsAcceptor::from(Arc::new(config));
+    let binding = bind_socket(SocketAddr::from(([127, 0, 0, 1], 0)))
+        .await
+        .unwrap();
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = binding.listener.accept().await.unwrap();
+            let acceptor = acceptor.clone();
+            tokio::spawn(async move {
+                let Ok(stream) = acceptor.accept(stream).await else {
+                    return;
+                };
+                let service = service_fn(|_| async {
+                    Ok::<_, Infallible>(Response::new(Full::new(Bytes::from("from upstream"))))
+                });
+                let _ = http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await;
+            });
+        }
+    });
+    (binding.port, certified.cert.pem())
+}
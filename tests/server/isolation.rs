@@ -0,0 +1,136 @@
+use pulcinella::{
+    client::Client,
+    server::{Mode, SequentialState, INSTANCE_HEADER},
+};
+
+use crate::helpers::{start_isolated_server, start_server_with, ServerPorts};
+
+#[tokio::test]
+async fn should_keep_instances_side_by_side() {
+    let server_ports = start_isolated_server(Mode::Mock).await;
+    let first = client_for(&server_ports).await;
+    let second = client_for(&server_ports).await;
+    first
+        .when(|when| when.path("/users"))
+        .then(|then| then.status(200).body("first"))
+        .send()
+        .await
+        .expect("Failed to install mock");
+    second
+        .when(|when| when.path("/users"))
+        .then(|then| then.status(201).body("second"))
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    let first_response = reqwest::get(format!("{}/users", first.url()))
+        .await
+        .expect("Failed to send request");
+    let second_response = reqwest::get(format!("{}/users", second.url()))
+        .await
+        .expect("Failed to send request");
+
+    assert_eq!(200, first_response.status());
+    assert_eq!("first", first_response.text().await.unwrap());
+    assert_eq!(201, second_response.status());
+    assert_eq!("second", second_response.text().await.unwrap());
+    let first_requests = first
+        .received_requests()
+        .await
+        .expect("Failed to fetch requests");
+    assert_eq!(1, first_requests.len());
+    assert_eq!("/users", first_requests[0].path);
+}
+
+#[tokio::test]
+async fn should_route_by_instance_header() {
+    let server_ports = start_isolated_server(Mode::Mock).await;
+    let mock_client = client_for(&server_ports).await;
+    mock_client
+        .when(|when| when.path("/users"))
+        .then(|then| then.status(200))
+        .send()
+        .await
+        .expect("Failed to install mock");
+    let instance = mock_client.url().rsplit('/').next().unwrap().to_string();
+
+    let response = reqwest::Client::new()
+        .get(format!("http://localhost:{}/users", server_ports.mock))
+        .header(INSTANCE_HEADER, instance)
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    assert_eq!(200, response.status());
+}
+
+#[tokio::test]
+async fn should_not_match_requests_for_unknown_instance() {
+    let server_ports = start_isolated_server(Mode::Mock).await;
+    let mock_client = client_for(&server_ports).await;
+    mock_client
+        .when(|when| when.path("/users"))
+        .then(|then| then.status(200))
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    let unrouted = reqwest::get(format!("http://localhost:{}/users", server_ports.mock))
+        .await
+        .expect("Failed to send request");
+    let unknown = reqwest::get(format!(
+        "http://localhost:{}/instance/unknown/users",
+        server_ports.mock
+    ))
+    .await
+    .expect("Failed to send request");
+
+    assert_eq!(404, unrouted.status());
+    assert_eq!(404, unknown.status());
+}
+
+#[tokio::test]
+async fn should_serve_each_instance_on_its_own_listener() {
+    let server_ports = start_server_with(Mode::Mock, |state| {
+        state.isolated().with_instance_listeners()
+    })
+    .await;
+    let first = client_for(&server_ports).await;
+    let second = client_for(&server_ports).await;
+    first
+        .when(|when| when.path("/users"))
+        .then(|then| then.status(200).body("first"))
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    let first_response = reqwest::get(format!("{}/users", first.url()))
+        .await
+        .expect("Failed to send request");
+    let second_response = reqwest::get(format!("{}/users", second.url()))
+        .await
+        .expect("Failed to send request");
+
+    assert_ne!(first.url(), second.url());
+    assert!(!first.url().ends_with(&format!(":{}", server_ports.mock)));
+    assert_eq!("first", first_response.text().await.unwrap());
+    assert_eq!(404, second_response.status());
+}
+
+#[tokio::test]
+async fn should_stop_listener_of_replaced_instance() {
+    let server_ports =
+        start_server_with(Mode::Mock, SequentialState::with_instance_listeners).await;
+    let first = client_for(&server_ports).await;
+    let _second = client_for(&server_ports).await;
+
+    let response = reqwest::get(format!("{}/users", first.url())).await;
+
+    assert!(response.is_err());
+}
+
+async fn client_for(server_ports: &ServerPorts) -> Client {
+    Client::new(&format!("http://localhost:{}", server_ports.control_plane))
+        .await
+        .expect("mock client failed to start")
+}
@@ -0,0 +1,176 @@
+use pulcinella::client::{Method, Mismatch, UnmatchedReport};
+
+use crate::helpers::{setup_server, Dsl};
+
+#[tokio::test]
+async fn should_record_matched_and_unmatched_requests() {
+    let Dsl {
+        control: mock_client,
+        reqwest_client: client,
+        ..
+    } = setup_server().await;
+    let users = mock_client
+        .when(|when| when.path("/users"))
+        .then(|then| then.status(200))
+        .send()
+        .await
+        .expect("Failed to install mock");
+    let orders = mock_client
+        .when(|when| when.path("/orders").method(Method::POST))
+        .then(|then| then.status(201))
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    for path in ["/users", "/missing", "/users"] {
+        client
+            .get(format!("{}{}", mock_client.url(), path))
+            .send()
+            .await
+            .expect("Failed to send request");
+    }
+
+    let requests = mock_client
+        .received_requests()
+        .await
+        .expect("Failed to fetch requests");
+    let paths = requests
+        .iter()
+        .map(|request| request.path.as_str())
+        .collect::<Vec<_>>();
+
+    assert_eq!(vec!["/users", "/missing", "/users"], paths);
+    assert_eq!(Some(users.id()), requests[0].matched.as_ref());
+    assert_eq!(None, requests[1].matched);
+    assert_eq!(2, users.hits().await.expect("Failed to fetch hits").len());
+    users.assert_called_times(2).await;
+    orders.assert_called_times(0).await;
+}
+
+#[tokio::test]
+async fn should_record_request_body_and_headers() {
+    let Dsl {
+        control: mock_client,
+        reqwest_client: client,
+        ..
+    } = setup_server().await;
+    let mock = mock_client
+        .when(|when| when.path("/orders").form_data("sku", "X"))
+        .then(|then| then.status(201))
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    client
+        .post(format!("{}/orders?draft=true", mock_client.url()))
+        .header("x-tenant", "acme")
+        .form(&[("sku", "X")])
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    let hits = mock.hits().await.expect("Failed to fetch hits");
+    assert_eq!("POST", hits[0].method);
+    assert_eq!(Some("draft=true"), hits[0].query.as_deref());
+    assert_eq!(b"sku=X".to_vec(), hits[0].body);
+    assert!(hits[0]
+        .headers
+        .contains(&("x-tenant".to_string(), "acme".to_string())));
+}
+
+#[tokio::test]
+#[should_panic(expected = "to be called 1 times but it was called 0 times")]
+async fn should_panic_when_call_count_differs() {
+    let Dsl {
+        control: mock_client,
+        ..
+    } = setup_server().await;
+    let mock = mock_client
+        .when(|when| when.path("/users"))
+        .then(|then| then.status(200))
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    mock.assert_called_times(1).await;
+}
+
+#[tokio::test]
+async fn should_report_closest_mock_in_404_body() {
+    let Dsl {
+        control: mock_client,
+        reqwest_client: client,
+        ..
+    } = setup_server().await;
+    let delete_user = mock_client
+        .when(|when| when.path("/users").method(Method::DELETE))
+        .then(|then| then.status(204))
+        .send()
+        .await
+        .expect("Failed to install mock");
+    mock_client
+        .when(|when| when.path("/orders").header("x-tenant", "acme"))
+        .then(|then| then.status(200))
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    let response = client
+        .get(format!("{}/users", mock_client.url()))
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    assert_eq!(404, response.status());
+    let report = response
+        .json::<UnmatchedReport>()
+        .await
+        .expect("Failed to parse report");
+    assert_eq!("/users", report.path);
+    assert_eq!(2, report.near_misses.len());
+    assert_eq!(delete_user.id(), &report.near_misses[0].mock);
+    assert_eq!(
+        vec![Mismatch {
+            field: "method".to_string(),
+            expected: "DELETE".to_string(),
+            actual: "GET".to_string(),
+        }],
+        report.near_misses[0].mismatches
+    );
+    assert_eq!(2, report.near_misses[1].mismatches.len());
+}
+
+#[tokio::test]
+async fn should_fetch_near_misses_for_unmatched_requests() {
+    let Dsl {
+        control: mock_client,
+        reqwest_client: client,
+        ..
+    } = setup_server().await;
+    let delete_user = mock_client
+        .when(|when| when.path("/users").method(Method::DELETE))
+        .then(|then| then.status(204))
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    client
+        .get(format!("{}/users", mock_client.url()))
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    let unmatched = mock_client
+        .unmatched_requests()
+        .await
+        .expect("Failed to fetch unmatched requests");
+    let closest = unmatched[0].closest_mock().expect("Expected a near miss");
+
+    assert_eq!(
+        format!(
+            "mock {} differed in method: expected DELETE, got GET",
+            delete_user.id()
+        ),
+        closest.to_string()
+    );
+}
@@ -0,0 +1,97 @@
+use std::time::Duration;
+
+use pulcinella::{
+    client::{Client, ClientError},
+    server::{Mode, SequentialState},
+};
+
+use crate::helpers::{start_server, start_server_with, ServerPorts};
+
+#[tokio::test]
+async fn should_remove_mocks_on_reset() {
+    let server_ports = start_server(Mode::Mock).await;
+    let mock_client = client_for(&server_ports).await;
+    mock_client
+        .when(|when| when.path("/users"))
+        .then(|then| then.status(200))
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    mock_client.reset().await.expect("Failed to reset mocks");
+    let response = reqwest::get(format!("{}/users", mock_client.url()))
+        .await
+        .expect("Failed to send request");
+
+    assert_eq!(404, response.status());
+}
+
+#[tokio::test]
+async fn should_delete_instance_when_guard_dropped() {
+    let server_ports = start_server_with(Mode::Mock, SequentialState::isolated).await;
+    let guard = client_for(&server_ports).await.guard();
+    guard
+        .when(|when| when.path("/users"))
+        .then(|then| then.status(200))
+        .send()
+        .await
+        .expect("Failed to install mock");
+    let url = guard.url();
+
+    drop(guard);
+
+    for _ in 0..50 {
+        let response = reqwest::get(format!("{url}/users"))
+            .await
+            .expect("Failed to send request");
+        if response.status() == 404 {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("Instance was not deleted when its guard was dropped");
+}
+
+#[tokio::test]
+async fn should_stop_listener_of_deleted_instance() {
+    let server_ports =
+        start_server_with(Mode::Mock, SequentialState::with_instance_listeners).await;
+    let mock_client = client_for(&server_ports).await;
+    let url = mock_client.url();
+
+    mock_client
+        .delete()
+        .await
+        .expect("Failed to delete instance");
+    let response = reqwest::get(format!("{url}/users")).await;
+
+    assert!(response.is_err());
+}
+
+#[tokio::test]
+async fn should_evict_idle_instances() {
+    let server_ports = start_server_with(Mode::Mock, |state| {
+        state.isolated().with_idle_ttl(Duration::from_millis(100))
+    })
+    .await;
+    let mock_client = client_for(&server_ports).await;
+    mock_client
+        .when(|when| when.path("/users"))
+        .then(|then| then.status(200))
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    tokio::time::sleep(Duration::from_millis(400)).await;
+
+    assert_eq!(
+        Err(ClientError::InstanceNoLongerValid),
+        mock_client.reset().await
+    );
+}
+
+async fn client_for(server_ports: &ServerPorts) -> Client {
+    Client::new(&format!("http://localhost:{}", server_ports.control_plane))
+        .await
+        .expect("mock client failed to start")
+}
@@ -4,5 +4,20 @@ cfg_if::cfg_if! {
         mod client_integration;
         mod helpers;
         mod server_safety;
+        mod cors;
+        mod journal;
+        mod intercept;
+        mod isolation;
+        mod lifecycle;
+        mod delays;
+        mod faults;
+        mod scenarios;
+        mod sequences;
+        mod templates;
+        mod passthrough;
+        mod recording;
+        mod reverse_proxy;
+        mod tls;
+        mod tunnel;
     }
 }
@@ -0,0 +1,121 @@
+use pulcinella::{
+    client::{Client, ClientError, Passthrough},
+    server::Mode,
+};
+
+use crate::helpers::start_server;
+
+#[tokio::test]
+async fn should_forward_matching_requests_upstream() {
+    let upstream = start_client().await;
+    let mock_client = start_client().await;
+    upstream
+        .when(|when| when.path("/users").query("page", "2"))
+        .then(|then| then.status(200).body("from upstream"))
+        .send()
+        .await
+        .expect("Failed to install mock");
+    mock_client
+        .when(|when| when.path("/users"))
+        .then(|then| then.passthrough(Passthrough::to(&upstream.url())))
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    let response = reqwest::get(format!("{}/users?page=2", mock_client.url()))
+        .await
+        .expect("Failed to send request");
+    let unmatched = reqwest::get(format!("{}/orders", mock_client.url()))
+        .await
+        .expect("Failed to send request");
+
+    assert_eq!(200, response.status());
+    assert_eq!("from upstream", response.text().await.unwrap());
+    assert_eq!(404, unmatched.status());
+}
+
+#[tokio::test]
+async fn should_modify_forwarded_request_and_response() {
+    let upstream = start_client().await;
+    let mock_client = start_client().await;
+    upstream
+        .when(|when| when.path("/users").header("x-forwarded-by", "pulcinella"))
+        .then(|then| then.status(200).header("x-upstream", "secret"))
+        .send()
+        .await
+        .expect("Failed to install mock");
+    mock_client
+        .when(|when| when.path("/users"))
+        .then(|then| {
+            then.passthrough(
+                Passthrough::to(&upstream.url())
+                    .request_header("x-forwarded-by", "pulcinella")
+                    .remove_request_header("authorization")
+                    .response_header("x-upstream", "redacted"),
+            )
+        })
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    let response = reqwest::Client::new()
+        .get(format!("{}/users", mock_client.url()))
+        .header("authorization", "Bearer token")
+        .send()
+        .await
+        .expect("Failed to send request");
+    let forwarded = upstream
+        .received_requests()
+        .await
+        .expect("Failed to fetch requests");
+
+    assert_eq!(200, response.status());
+    assert_eq!(
+        Some("redacted"),
+        response
+            .headers()
+            .get("x-upstream")
+            .and_then(|v| v.to_str().ok())
+    );
+    assert!(!forwarded[0]
+        .headers
+        .iter()
+        .any(|(name, _)| name == "authorization"));
+}
+
+#[tokio::test]
+async fn should_answer_bad_gateway_when_upstream_down() {
+    let mock_client = start_client().await;
+    mock_client
+        .when(|when| when.path("/users"))
+        .then(|then| then.passthrough(Passthrough::to("http://localhost:1")))
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    let response = reqwest::get(format!("{}/users", mock_client.url()))
+        .await
+        .expect("Failed to send request");
+
+    assert_eq!(502, response.status());
+}
+
+#[tokio::test]
+async fn should_reject_non_http_upstream() {
+    let mock_client = start_client().await;
+
+    let result = mock_client
+        .when(|when| when.path("/users"))
+        .then(|then| then.passthrough(Passthrough::to("ftp://localhost")))
+        .send()
+        .await;
+
+    assert_eq!(Some(ClientError::FailedToInstallMockRule), result.err());
+}
+
+async fn start_client() -> Client {
+    let server_ports = start_server(Mode::Mock).await;
+    Client::new(&format!("http://localhost:{}", server_ports.control_plane))
+        .await
+        .expect("mock client failed to start")
+}
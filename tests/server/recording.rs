@@ -0,0 +1,175 @@
+use pulcinella::{
+    client::{Client, Method},
+    server::Mode,
+};
+
+use crate::helpers::start_server;
+
+#[tokio::test]
+async fn should_record_each_distinct_exchange_once() {
+    let upstream = start_upstream().await;
+    let (recorder, proxy) = start_recorder().await;
+
+    for _ in 0..2 {
+        let response = proxy
+            .get(format!("{}/users", upstream.url()))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(200, response.status());
+        assert_eq!("all users", response.text().await.unwrap());
+    }
+    proxy
+        .post(format!("{}/users", upstream.url()))
+        .json(&serde_json::json!({"name": "Ann"}))
+        .send()
+        .await
+        .expect("Failed to send request");
+    let recorded = recorder
+        .recorded_mocks()
+        .await
+        .expect("Failed to fetch recordings");
+
+    assert_eq!(2, recorded.len());
+    assert!(recorded
+        .iter()
+        .any(|rule| rule.then.status == 201 && rule.then.body == b"created"));
+}
+
+#[tokio::test]
+async fn should_record_each_query_separately() {
+    let upstream = start_upstream().await;
+    let (recorder, proxy) = start_recorder().await;
+
+    for page in ["1", "2"] {
+        let response = proxy
+            .get(format!("{}/pages?page={page}", upstream.url()))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(format!("page {page}"), response.text().await.unwrap());
+    }
+    let recorded = recorder
+        .recorded_mocks()
+        .await
+        .expect("Failed to fetch recordings");
+
+    assert_eq!(2, recorded.len());
+}
+
+#[tokio::test]
+async fn should_not_answer_extra_query_parameters_from_a_recording() {
+    let upstream = start_upstream().await;
+    let (recorder, proxy) = start_recorder().await;
+    proxy
+        .get(format!("{}/users", upstream.url()))
+        .send()
+        .await
+        .expect("Failed to send request");
+    let recorded = recorder
+        .recorded_mocks()
+        .await
+        .expect("Failed to fetch recordings");
+
+    proxy
+        .get(format!("{}/users?x=1", upstream.url()))
+        .send()
+        .await
+        .expect("Failed to send request");
+    let rerecorded = recorder
+        .recorded_mocks()
+        .await
+        .expect("Failed to fetch recordings");
+
+    assert_eq!(1, recorded.len());
+    assert_eq!(2, rerecorded.len());
+
+    let server_ports = start_server(Mode::Playback).await;
+    let player = Client::new(&format!("http://localhost:{}", server_ports.control_plane))
+        .await
+        .expect("mock client failed to start");
+    for rule in recorded {
+        player.install(rule).await.expect("Failed to install mock");
+    }
+    let replayed = reqwest::get(format!("{}/users", player.url()))
+        .await
+        .expect("Failed to send request");
+    let unrecorded = reqwest::get(format!("{}/users?x=1", player.url()))
+        .await
+        .expect("Failed to send request");
+
+    assert_eq!(200, replayed.status());
+    assert_eq!(500, unrecorded.status());
+}
+
+#[tokio::test]
+async fn should_play_back_recordings_and_fail_unrecorded_requests() {
+    let upstream = start_upstream().await;
+    let (recorder, proxy) = start_recorder().await;
+    proxy
+        .get(format!("{}/users", upstream.url()))
+        .send()
+        .await
+        .expect("Failed to send request");
+    let recorded = recorder
+        .recorded_mocks()
+        .await
+        .expect("Failed to fetch recordings");
+
+    let server_ports = start_server(Mode::Playback).await;
+    let player = Client::new(&format!("http://localhost:{}", server_ports.control_plane))
+        .await
+        .expect("mock client failed to start");
+    for rule in recorded {
+        player.install(rule).await.expect("Failed to install mock");
+    }
+    let replayed = reqwest::get(format!("{}/users", player.url()))
+        .await
+        .expect("Failed to send request");
+    let unrecorded = reqwest::get(format!("{}/orders", player.url()))
+        .await
+        .expect("Failed to send request");
+
+    assert_eq!(200, replayed.status());
+    assert_eq!("all users", replayed.text().await.unwrap());
+    assert_eq!(500, unrecorded.status());
+}
+
+async fn start_upstream() -> Client {
+    let server_ports = start_server(Mode::Mock).await;
+    let upstream = Client::new(&format!("http://localhost:{}", server_ports.control_plane))
+        .await
+        .expect("mock client failed to start");
+    upstream
+        .when(|when| when.path("/users").method(Method::GET))
+        .then(|then| then.status(200).body("all users"))
+        .send()
+        .await
+        .expect("Failed to install mock");
+    upstream
+        .when(|when| {
+            when.path("/users")
+                .json_body(serde_json::json!({"name": "Ann"}))
+        })
+        .then(|then| then.status(201).body("created"))
+        .send()
+        .await
+        .expect("Failed to install mock");
+    upstream
+        .when(|when| when.path("/pages").method(Method::GET))
+        .then(|then| then.status(200).body("page {{query.page}}").templated())
+        .send()
+        .await
+        .expect("Failed to install mock");
+    upstream
+}
+
+async fn start_recorder() -> (Client, reqwest::Client) {
+    let server_ports = start_server(Mode::Record).await;
+    let recorder = Client::new(&format!("http://localhost:{}", server_ports.control_plane))
+        .await
+        .expect("mock client failed to start");
+    let proxy = reqwest::Proxy::http(format!("http://localhost:{}/", server_ports.mock)).unwrap();
+    let proxy = reqwest::ClientBuilder::new().proxy(proxy).build().unwrap();
+    (recorder, proxy)
+}
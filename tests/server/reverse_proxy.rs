@@ -0,0 +1,51 @@
+use pulcinella::{client::Client, server::Mode};
+
+use crate::helpers::{start_server, start_server_with};
+
+#[tokio::test]
+async fn should_forward_unmatched_requests_to_upstream() {
+    let server_ports = start_server(Mode::Mock).await;
+    let upstream = Client::new(&format!("http://localhost:{}", server_ports.control_plane))
+        .await
+        .expect("mock client failed to start");
+    upstream
+        .when(|when| when.path("/users").query("page", "2"))
+        .then(|then| then.status(200).body("from upstream"))
+        .send()
+        .await
+        .expect("Failed to install mock");
+    let upstream_url = upstream.url();
+    let server_ports = start_server_with(Mode::Proxy, |state| {
+        state
+            .with_upstream(&upstream_url)
+            .expect("Invalid upstream")
+    })
+    .await;
+
+    let response = reqwest::get(format!(
+        "http://localhost:{}/users?page=2",
+        server_ports.mock
+    ))
+    .await
+    .expect("Failed to send request");
+    let forwarded = upstream
+        .received_requests()
+        .await
+        .expect("Failed to fetch requests");
+
+    assert_eq!(200, response.status());
+    assert_eq!("from upstream", response.text().await.unwrap());
+    let host = forwarded[0]
+        .headers
+        .iter()
+        .find(|(name, _)| name == "host")
+        .map(|(_, value)| value.as_str());
+    assert_eq!(upstream_url.strip_prefix("http://"), host);
+}
+
+#[tokio::test]
+async fn should_reject_non_http_upstream() {
+    let result = pulcinella::server::SequentialState::new(0).with_upstream("ftp://localhost");
+
+    assert!(result.is_err());
+}
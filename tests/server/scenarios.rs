@@ -0,0 +1,142 @@
+use pulcinella::client::{Client, ClientError, Method, Scenario, SCENARIO_STARTED};
+
+use crate::helpers::{setup_server, Dsl};
+
+#[tokio::test]
+async fn should_follow_scenario_transitions() {
+    let Dsl {
+        control: mock_client,
+        reqwest_client: client,
+        ..
+    } = setup_server().await;
+    install_job_scenario(&mock_client).await;
+
+    let pending = client
+        .get(format!("{}/job", mock_client.url()))
+        .send()
+        .await
+        .expect("Failed to send request");
+    client
+        .post(format!("{}/job/complete", mock_client.url()))
+        .send()
+        .await
+        .expect("Failed to send request");
+    let done = client
+        .get(format!("{}/job", mock_client.url()))
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    assert_eq!("pending", pending.text().await.unwrap());
+    assert_eq!("done", done.text().await.unwrap());
+}
+
+#[tokio::test]
+async fn should_inspect_and_reset_scenarios() {
+    let Dsl {
+        control: mock_client,
+        reqwest_client: client,
+        ..
+    } = setup_server().await;
+    install_job_scenario(&mock_client).await;
+    client
+        .post(format!("{}/job/complete", mock_client.url()))
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    let completed = mock_client
+        .scenarios()
+        .await
+        .expect("Failed to fetch scenarios");
+    mock_client
+        .reset_scenarios()
+        .await
+        .expect("Failed to reset scenarios");
+    let reset = mock_client
+        .scenarios()
+        .await
+        .expect("Failed to fetch scenarios");
+
+    assert_eq!(vec![scenario("job", "Completed")], completed);
+    assert_eq!(vec![scenario("job", SCENARIO_STARTED)], reset);
+}
+
+#[tokio::test]
+async fn should_report_scenario_state_in_near_misses() {
+    let Dsl {
+        control: mock_client,
+        reqwest_client: client,
+        ..
+    } = setup_server().await;
+    install_job_scenario(&mock_client).await;
+    client
+        .post(format!("{}/job/complete", mock_client.url()))
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    client
+        .post(format!("{}/job/complete", mock_client.url()))
+        .send()
+        .await
+        .expect("Failed to send request");
+    let unmatched = mock_client
+        .unmatched_requests()
+        .await
+        .expect("Failed to fetch unmatched requests");
+
+    let closest = unmatched[0].closest_mock().expect("Expected a near miss");
+    assert_eq!(
+        "scenario job: expected Started, got Completed",
+        closest.mismatches[0].to_string()
+    );
+}
+
+#[tokio::test]
+async fn should_reject_transition_outside_scenario() {
+    let Dsl {
+        control: mock_client,
+        ..
+    } = setup_server().await;
+
+    let result = mock_client
+        .when(|when| when.path("/job"))
+        .then(|then| then.status(200).transition_to("Completed"))
+        .send()
+        .await;
+
+    assert_eq!(Some(ClientError::FailedToInstallMockRule), result.err());
+}
+
+async fn install_job_scenario(mock_client: &Client) {
+    mock_client
+        .when(|when| when.path("/job").in_scenario("job", SCENARIO_STARTED))
+        .then(|then| then.status(200).body("pending"))
+        .send()
+        .await
+        .expect("Failed to install mock");
+    mock_client
+        .when(|when| {
+            when.path("/job/complete")
+                .method(Method::POST)
+                .in_scenario("job", SCENARIO_STARTED)
+        })
+        .then(|then| then.status(204).transition_to("Completed"))
+        .send()
+        .await
+        .expect("Failed to install mock");
+    mock_client
+        .when(|when| when.path("/job").in_scenario("job", "Completed"))
+        .then(|then| then.status(200).body("done"))
+        .send()
+        .await
+        .expect("Failed to install mock");
+}
+
+fn scenario(name: &str, state: &str) -> Scenario {
+    Scenario {
+        name: name.to_string(),
+        state: state.to_string(),
+    }
+}
@@ -0,0 +1,138 @@
+use crate::helpers::{setup_server, Dsl};
+
+#[tokio::test]
+async fn should_repeat_last_response_of_sequence() {
+    let Dsl {
+        control: mock_client,
+        reqwest_client: client,
+        ..
+    } = setup_server().await;
+    mock_client
+        .when(|when| when.path("/flaky"))
+        .then(|then| {
+            then.status(503)
+                .followed_by(|then| then.status(503))
+                .followed_by(|then| then.status(200))
+        })
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    let mut statuses = vec![];
+    for _ in 0..4 {
+        let response = client
+            .get(format!("{}/flaky", mock_client.url()))
+            .send()
+            .await
+            .expect("Failed to send request");
+        statuses.push(response.status().as_u16());
+    }
+
+    assert_eq!(vec![503, 503, 200, 200], statuses);
+}
+
+#[tokio::test]
+async fn should_cycle_through_sequence() {
+    let Dsl {
+        control: mock_client,
+        reqwest_client: client,
+        ..
+    } = setup_server().await;
+    mock_client
+        .when(|when| when.path("/toggle"))
+        .then(|then| {
+            then.status(200)
+                .body("on")
+                .followed_by(|then| then.status(200).body("off"))
+                .cycle()
+        })
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    let mut bodies = vec![];
+    for _ in 0..3 {
+        let response = client
+            .get(format!("{}/toggle", mock_client.url()))
+            .send()
+            .await
+            .expect("Failed to send request");
+        bodies.push(response.text().await.unwrap());
+    }
+
+    assert_eq!(vec!["on", "off", "on"], bodies);
+}
+
+#[tokio::test]
+async fn should_fall_through_once_mock_used_up() {
+    let Dsl {
+        control: mock_client,
+        reqwest_client: client,
+        ..
+    } = setup_server().await;
+    mock_client
+        .when(|when| when.path("/token"))
+        .then(|then| then.status(200).body("fallback"))
+        .send()
+        .await
+        .expect("Failed to install mock");
+    mock_client
+        .when(|when| when.path("/token").header("x-attempt", "first"))
+        .then(|then| then.status(201).body("once"))
+        .times(1)
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    let mut bodies = vec![];
+    for _ in 0..2 {
+        let response = client
+            .get(format!("{}/token", mock_client.url()))
+            .header("x-attempt", "first")
+            .send()
+            .await
+            .expect("Failed to send request");
+        bodies.push(response.text().await.unwrap());
+    }
+
+    assert_eq!(vec!["once", "fallback"], bodies);
+}
+
+#[tokio::test]
+async fn should_report_used_up_mock_as_near_miss() {
+    let Dsl {
+        control: mock_client,
+        reqwest_client: client,
+        ..
+    } = setup_server().await;
+    mock_client
+        .when(|when| when.path("/once"))
+        .then(|then| then.status(200))
+        .times(1)
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    let first = client
+        .get(format!("{}/once", mock_client.url()))
+        .send()
+        .await
+        .expect("Failed to send request");
+    let second = client
+        .get(format!("{}/once", mock_client.url()))
+        .send()
+        .await
+        .expect("Failed to send request");
+    let unmatched = mock_client
+        .unmatched_requests()
+        .await
+        .expect("Failed to fetch unmatched requests");
+
+    assert_eq!(200, first.status());
+    assert_eq!(404, second.status());
+    let closest = unmatched[0].closest_mock().expect("Expected a near miss");
+    assert_eq!(
+        "times: expected at most 1 uses, got 1 uses",
+        closest.mismatches[0].to_string()
+    );
+}
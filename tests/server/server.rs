@@ -31,7 +31,7 @@ async fn should_proxy_through_to_real_server() {
     let client = setup_server().await;
 
     let response = client
-        .get(&format!("http://localhost:{}/", proxy_port))
+        .get(format!("http://localhost:{}/", proxy_port))
         .send()
         .await
         .expect("Failed to send request");
@@ -62,7 +62,7 @@ async fn should_proxy_through_headers_to_real_server() {
     let client = setup_server().await;
 
     let response = client
-        .get(&format!("http://localhost:{}/", proxy_port))
+        .get(format!("http://localhost:{}/", proxy_port))
         .header(header_name.clone(), header_value.clone())
         .send()
         .await
@@ -87,7 +87,7 @@ async fn should_proxy_through_post_and_body() {
     let client = setup_server().await;
 
     let response = client
-        .post(&format!("http://localhost:{}/", proxy_port))
+        .post(format!("http://localhost:{}/", proxy_port))
         .body(body.clone())
         .send()
         .await
@@ -107,7 +107,7 @@ async fn should_respond_with_bad_request_if_host_header_not_correctly_formed() {
     let client = setup_server().await;
 
     let response = client
-        .post(&format!("http://localhost:{}/", proxy_port))
+        .post(format!("http://localhost:{}/", proxy_port))
         .header("host", "not a url")
         .send()
         .await
@@ -127,7 +127,7 @@ async fn should_respond_with_upstream_not_found_if_server_not_available() {
     let client = setup_server().await;
 
     let response = client
-        .post(&format!("http://localhost:{}/", 1))
+        .post(format!("http://localhost:{}/", 1))
         .send()
         .await
         .expect("Failed to send request");
@@ -150,7 +150,7 @@ async fn should_respond_with_upstream_for_different_path() {
     let client = setup_server().await;
 
     let response = client
-        .post(&format!("http://localhost:{}/{}", proxy_port, path))
+        .post(format!("http://localhost:{}/{}", proxy_port, path))
         .send()
         .await
         .expect("Failed to send request");
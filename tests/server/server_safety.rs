@@ -9,7 +9,7 @@ async fn should_handle_no_body_passed_to_control_plane() {
 
     let response = dsl
         .client
-        .post(&format!(
+        .post(format!(
             "http://localhost:{}/",
             dsl.server_ports.control_plane
         ))
@@ -26,7 +26,7 @@ async fn should_handle_cors_for_control_port() {
 
     let response = dsl
         .client
-        .request(Method::OPTIONS, &format!(
+        .request(Method::OPTIONS, format!(
             "http://localhost:{}/",
             dsl.server_ports.control_plane
         ))
@@ -45,7 +45,7 @@ async fn should_handle_cors_for_mock_port() {
 
     let response = dsl
         .client
-        .request(Method::OPTIONS, &format!(
+        .request(Method::OPTIONS, format!(
             "http://localhost:{}/",
             dsl.server_ports.mock
         ))
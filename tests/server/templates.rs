@@ -0,0 +1,193 @@
+use pulcinella::client::{JsonCondition, Method};
+use serde_json::json;
+
+use crate::helpers::{setup_server, Dsl};
+
+#[tokio::test]
+async fn should_fill_response_from_request() {
+    let Dsl {
+        control: mock_client,
+        reqwest_client: client,
+        ..
+    } = setup_server().await;
+    mock_client
+        .when(|when| when.path_template("/users/{id}"))
+        .then(|then| {
+            then.status(200)
+                .header("location", "/users/{{path.id}}")
+                .body(r#"{"id":"{{path.id}}","q":"{{query.q}}","agent":"{{header.x-agent}}"}"#)
+                .templated()
+        })
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    let response = client
+        .get(format!("{}/users/42?q=hi", mock_client.url()))
+        .header("x-agent", "tests")
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    assert_eq!(
+        Some("/users/42"),
+        response
+            .headers()
+            .get("location")
+            .and_then(|v| v.to_str().ok())
+    );
+    assert_eq!(
+        r#"{"id":"42","q":"hi","agent":"tests"}"#,
+        response.text().await.unwrap()
+    );
+}
+
+#[tokio::test]
+async fn should_fill_json_fields_and_counter() {
+    let Dsl {
+        control: mock_client,
+        reqwest_client: client,
+        ..
+    } = setup_server().await;
+    mock_client
+        .when(|when| {
+            when.path("/greet")
+                .method(Method::POST)
+                .json_path("$.user.name", JsonCondition::Exists)
+        })
+        .then(|then| {
+            then.status(200)
+                .body("hello {{json.user.name}} #{{counter}}")
+                .templated()
+        })
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    let mut bodies = vec![];
+    for _ in 0..2 {
+        let response = client
+            .post(format!("{}/greet", mock_client.url()))
+            .json(&json!({"user": {"name": "ann"}}))
+            .send()
+            .await
+            .expect("Failed to send request");
+        bodies.push(response.text().await.unwrap());
+    }
+
+    assert_eq!(vec!["hello ann #1", "hello ann #2"], bodies);
+}
+
+#[tokio::test]
+async fn should_fill_helpers_and_leave_unknown_empty() {
+    let Dsl {
+        control: mock_client,
+        reqwest_client: client,
+        ..
+    } = setup_server().await;
+    mock_client
+        .when(|when| when.path("/helpers"))
+        .then(|then| {
+            then.status(200)
+                .body("{{uuid}}|{{now}}|{{query.missing}}")
+                .templated()
+        })
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    let body = client
+        .get(format!("{}/helpers", mock_client.url()))
+        .send()
+        .await
+        .expect("Failed to send request")
+        .text()
+        .await
+        .unwrap();
+    let parts = body.split('|').collect::<Vec<_>>();
+
+    assert_eq!(36, parts[0].len());
+    assert!(parts[1].contains('T'), "{} is not a timestamp", parts[1]);
+    assert_eq!("", parts[2]);
+}
+
+#[tokio::test]
+async fn should_leave_placeholders_alone_unless_templated() {
+    let Dsl {
+        control: mock_client,
+        reqwest_client: client,
+        ..
+    } = setup_server().await;
+    mock_client
+        .when(|when| when.path("/static"))
+        .then(|then| then.status(200).body("{{path}}"))
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    let body = client
+        .get(format!("{}/static", mock_client.url()))
+        .send()
+        .await
+        .expect("Failed to send request")
+        .text()
+        .await
+        .unwrap();
+
+    assert_eq!("{{path}}", body);
+}
+
+#[tokio::test]
+async fn should_drop_templated_headers_with_invalid_values() {
+    let Dsl {
+        control: mock_client,
+        reqwest_client: client,
+        ..
+    } = setup_server().await;
+    let mock = mock_client
+        .when(|when| when.path("/echo"))
+        .then(|then| {
+            then.status(200)
+                .header("x-echo", "{{query.v}}")
+                .body("{{query.v}}")
+                .templated()
+        })
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    let response = client
+        .get(format!("{}/echo?v=a%0Ab", mock_client.url()))
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    assert_eq!(200, response.status());
+    assert_eq!(None, response.headers().get("x-echo"));
+    assert_eq!("a\nb", response.text().await.unwrap());
+    mock.assert_called_times(1).await;
+}
+
+#[tokio::test]
+async fn should_fail_responses_with_invalid_headers() {
+    let Dsl {
+        control: mock_client,
+        reqwest_client: client,
+        ..
+    } = setup_server().await;
+    let mock = mock_client
+        .when(|when| when.path("/broken"))
+        .then(|then| then.status(200).header("x-broken", "a\nb"))
+        .send()
+        .await
+        .expect("Failed to install mock");
+
+    let response = client
+        .get(format!("{}/broken", mock_client.url()))
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    assert_eq!(500, response.status());
+    mock.assert_called_times(1).await;
+}
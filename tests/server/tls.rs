@@ -0,0 +1,55 @@
+use pulcinella::{
+    client::{Client, ClientError},
+    server::{Mode, TlsIdentity},
+};
+
+use crate::helpers::start_server_with;
+
+#[tokio::test]
+async fn should_serve_control_and_mock_ports_over_https() {
+    let identity = TlsIdentity::self_signed().unwrap();
+    let trusted = identity.cert_pem().to_string();
+    let server_ports = start_server_with(Mode::Mock, |state| state.with_tls(identity)).await;
+
+    let mock_client = Client::with_ca(
+        &format!("https://localhost:{}", server_ports.control_plane),
+        &trusted,
+    )
+    .await
+    .expect("mock client failed to start");
+    mock_client
+        .when(|when| when.path("/users"))
+        .then(|then| then.status(200).body("over tls"))
+        .send()
+        .await
+        .expect("Failed to install mock");
+    let response = reqwest::ClientBuilder::new()
+        .add_root_certificate(reqwest::Certificate::from_pem(trusted.as_bytes()).unwrap())
+        .build()
+        .unwrap()
+        .get(format!("{}/users", mock_client.url()))
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    assert!(mock_client.url().starts_with("https://"));
+    assert_eq!(200, response.status());
+    assert_eq!("over tls", response.text().await.unwrap());
+}
+
+#[tokio::test]
+async fn should_not_trust_self_signed_control_plane_by_default() {
+    let identity = TlsIdentity::self_signed().unwrap();
+    let server_ports = start_server_with(Mode::Mock, |state| state.with_tls(identity)).await;
+
+    let result = Client::new(&format!("https://localhost:{}", server_ports.control_plane)).await;
+
+    assert_eq!(Some(ClientError::FailedToConnectToMockServer), result.err());
+}
+
+#[tokio::test]
+async fn should_reject_invalid_ca() {
+    let result = Client::with_ca("https://localhost:1", "not a certificate").await;
+
+    assert_eq!(Some(ClientError::InvalidCertificate), result.err());
+}
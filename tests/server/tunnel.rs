@@ -0,0 +1,62 @@
+use std::net::SocketAddr;
+
+use pulcinella::server::{bind_socket, Mode};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+use crate::helpers::start_server;
+
+#[tokio::test]
+async fn should_tunnel_bytes_to_connect_target() {
+    let echo_port = start_echo_server().await;
+    let server_ports = start_server(Mode::Proxy).await;
+
+    let (mut stream, head) = send_connect(server_ports.mock, echo_port).await;
+    stream.write_all(b"ping").await.unwrap();
+    let mut echoed = [0; 4];
+    stream.read_exact(&mut echoed).await.unwrap();
+
+    assert!(head.starts_with("HTTP/1.1 200"), "{head}");
+    assert_eq!(b"ping", &echoed);
+}
+
+#[tokio::test]
+async fn should_answer_bad_gateway_when_connect_target_down() {
+    let server_ports = start_server(Mode::Proxy).await;
+
+    let (_, head) = send_connect(server_ports.mock, 1).await;
+
+    assert!(head.starts_with("HTTP/1.1 502"), "{head}");
+}
+
+async fn send_connect(proxy_port: u16, target_port: u16) -> (TcpStream, String) {
+    let mut stream = TcpStream::connect(("127.0.0.1", proxy_port)).await.unwrap();
+    let target = format!("127.0.0.1:{target_port}");
+    stream
+        .write_all(format!("CONNECT {target} HTTP/1.1\r\nHost: {target}\r\n\r\n").as_bytes())
+        .await
+        .unwrap();
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        head.push(stream.read_u8().await.unwrap());
+    }
+    (stream, String::from_utf8(head).unwrap())
+}
+
+async fn start_echo_server() -> u16 {
+    let binding = bind_socket(SocketAddr::from(([127, 0, 0, 1], 0)))
+        .await
+        .unwrap();
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = binding.listener.accept().await.unwrap();
+            tokio::spawn(async move {
+                let (mut reader, mut writer) = stream.split();
+                let _ = tokio::io::copy(&mut reader, &mut writer).await;
+            });
+        }
+    });
+    binding.port
+}